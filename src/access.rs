@@ -0,0 +1,167 @@
+use lru::LruCache;
+use std::collections::hash_map::RandomState;
+use std::collections::{BTreeMap, HashMap};
+use std::error::Error;
+use std::fs;
+use std::hash::{BuildHasher, Hash, Hasher};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+/// Dataset rule that applies to all datasets without a rule of their own, and user entry that
+/// allows every authenticated user.
+pub const ANY: &str = "*";
+
+// Number of accepted basic credentials that are remembered
+const MAX_VERIFIED_CREDENTIALS: usize = 1000;
+
+/// An access file looks like
+///
+/// ```toml
+/// # users and bcrypt password hashes, as created by `htpasswd -B`
+/// htpasswd = "users.htpasswd"
+///
+/// # bearer tokens, e.g. for scripts
+/// [tokens]
+/// pipeline = "d6f0c1a2b7e94b5c"
+///
+/// # users that may see a dataset, "*" for all datasets without a rule
+/// [datasets]
+/// tumor = ["alice", "pipeline"]
+/// "*" = ["*"]
+/// ```
+#[derive(Deserialize)]
+struct AccessConfig {
+    htpasswd: Option<PathBuf>,
+    #[serde(default)]
+    tokens: BTreeMap<String, String>,
+    datasets: Option<BTreeMap<String, Vec<String>>>,
+}
+
+/// Known users and tokens of the server and the datasets they may see.
+pub struct Access {
+    // User names and bcrypt hashes of their passwords
+    users: HashMap<String, String>,
+    // Tokens and the user they belong to
+    tokens: Vec<(String, String)>,
+    datasets: Option<BTreeMap<String, Vec<String>>>,
+    // Checking a bcrypt hash takes long, so accepted basic credentials are remembered by their
+    // hash. The hasher has a random key, so the hashes can't be computed outside of the server.
+    verified: Mutex<LruCache<u64, String>>,
+    credentials_hasher: RandomState,
+}
+
+impl Access {
+    /// Reads the access file. A relative htpasswd path is resolved against its directory.
+    pub fn from_file(path: &Path) -> Result<Access, Box<dyn Error>> {
+        let config: AccessConfig = toml::from_str(&fs::read_to_string(path)?)?;
+        let users = match config.htpasswd {
+            Some(htpasswd) => read_htpasswd(
+                &path
+                    .parent()
+                    .unwrap_or_else(|| Path::new(""))
+                    .join(htpasswd),
+            )?,
+            None => HashMap::new(),
+        };
+
+        Ok(Access {
+            users: users,
+            tokens: config
+                .tokens
+                .into_iter()
+                .map(|(user, token)| (token, user))
+                .collect(),
+            datasets: config.datasets,
+            verified: Mutex::new(LruCache::new(MAX_VERIFIED_CREDENTIALS)),
+            credentials_hasher: RandomState::new(),
+        })
+    }
+
+    /// Returns the user of an `Authorization` header value, which is either
+    /// `Bearer <token>` or `Basic <base64 of user:password>`.
+    pub fn authenticate(&self, authorization: &str) -> Option<String> {
+        let mut parts = authorization.trim().splitn(2, ' ');
+        let scheme = parts.next()?;
+        let credentials = parts.next()?.trim();
+
+        if scheme.eq_ignore_ascii_case("bearer") {
+            self.tokens
+                .iter()
+                .find(|(token, _)| constant_time_eq(token.as_bytes(), credentials.as_bytes()))
+                .map(|(_, user)| user.clone())
+        } else if scheme.eq_ignore_ascii_case("basic") {
+            let key = self.credentials_key(credentials);
+            if let Some(user) = self.verified.lock().unwrap().get(&key) {
+                return Some(user.clone());
+            }
+
+            let decoded = String::from_utf8(base64::decode(credentials).ok()?).ok()?;
+            let mut user_password = decoded.splitn(2, ':');
+            let user = user_password.next()?;
+            let password = user_password.next()?;
+
+            let hash = self.users.get(user)?;
+            if bcrypt::verify(password, hash).unwrap_or(false) {
+                self.verified.lock().unwrap().put(key, user.to_owned());
+                Some(user.to_owned())
+            } else {
+                None
+            }
+        } else {
+            None
+        }
+    }
+
+    fn credentials_key(&self, credentials: &str) -> u64 {
+        let mut hasher = self.credentials_hasher.build_hasher();
+        credentials.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    /// Whether a user may see a dataset. Without dataset rules every user may see everything,
+    /// otherwise datasets without a rule and without a `"*"` rule are hidden.
+    pub fn may_access(&self, user: &str, dataset: &str) -> bool {
+        let datasets = match &self.datasets {
+            Some(datasets) => datasets,
+            None => return true,
+        };
+
+        match datasets.get(dataset).or_else(|| datasets.get(ANY)) {
+            Some(users) => users.iter().any(|u| u == user || u == ANY),
+            None => false,
+        }
+    }
+}
+
+/// Reads `user:hash` lines of an htpasswd file. Only bcrypt hashes are supported.
+pub fn read_htpasswd(path: &Path) -> Result<HashMap<String, String>, Box<dyn Error>> {
+    let mut users = HashMap::new();
+
+    for line in fs::read_to_string(path)?.lines() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let mut fields = line.splitn(2, ':');
+        let user = fields.next().unwrap();
+        let hash = match fields.next() {
+            Some(hash) if hash.starts_with("$2") => hash,
+            _ => {
+                return Err(format!(
+                    "user '{}' in {} has no bcrypt password hash, create it with htpasswd -B",
+                    user,
+                    path.display()
+                )
+                .into())
+            }
+        };
+        users.insert(user.to_owned(), hash.to_owned());
+    }
+
+    Ok(users)
+}
+
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |diff, (x, y)| diff | (x ^ y)) == 0
+}
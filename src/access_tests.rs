@@ -0,0 +1,167 @@
+use super::*;
+use access::read_htpasswd;
+use rocket::http::Header;
+use rocket::local::Client;
+
+fn write_access_file(dir: &Path, datasets: &str) -> PathBuf {
+    fs::create_dir_all(dir).unwrap();
+    fs::write(
+        dir.join("users.htpasswd"),
+        format!(
+            "alice:{}\nbob:{}\n",
+            bcrypt::hash("alice-password", 4).unwrap(),
+            bcrypt::hash("bob-password", 4).unwrap()
+        ),
+    )
+    .unwrap();
+
+    let path = dir.join("access.toml");
+    fs::write(
+        &path,
+        format!(
+            "htpasswd = \"users.htpasswd\"\n\n[tokens]\npipeline = \"test-token\"\n\n{}",
+            datasets
+        ),
+    )
+    .unwrap();
+    path
+}
+
+fn basic(user: &str, password: &str) -> String {
+    format!(
+        "Basic {}",
+        base64::encode(&format!("{}:{}", user, password))
+    )
+}
+
+fn client(access: &Path) -> Client {
+    let dataset = Datasets::single(
+        DatasetConfig {
+            reference: PathBuf::from("tests/resources/ref.fa"),
+            alignments: PathBuf::from("tests/resources/test.bam"),
+            variants: PathBuf::from("tests/resources/report-test.vcf.gz"),
+            annotation: None,
+            description: None,
+            tracks: None,
+            insert_size: None,
+        },
+        4,
+    )
+    .unwrap();
+    let server = rocket::ignite()
+        .manage(dataset)
+        .manage(Some(Access::from_file(access).unwrap()))
+        .mount("/api/v1", routes![datasets, dataset_reference])
+        .register(catchers![unauthorized]);
+    Client::new(server).unwrap()
+}
+
+#[test]
+fn authenticate_test() {
+    let dir = std::env::temp_dir().join(format!("authenticate-test-{}", std::process::id()));
+    let access = Access::from_file(&write_access_file(&dir, "")).unwrap();
+
+    assert_eq!(
+        access.authenticate(&basic("alice", "alice-password")),
+        Some(String::from("alice"))
+    );
+    // The second time the credentials are taken from the cache
+    assert_eq!(
+        access.authenticate(&basic("alice", "alice-password")),
+        Some(String::from("alice"))
+    );
+    assert_eq!(access.authenticate(&basic("alice", "bob-password")), None);
+    assert_eq!(access.authenticate(&basic("carol", "alice-password")), None);
+    assert_eq!(
+        access.authenticate("Bearer test-token"),
+        Some(String::from("pipeline"))
+    );
+    assert_eq!(access.authenticate("Bearer test-toke"), None);
+    assert_eq!(access.authenticate("test-token"), None);
+
+    // Without dataset rules all users may see all datasets
+    assert!(access.may_access("bob", "tumor"));
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn dataset_access_test() {
+    let dir = std::env::temp_dir().join(format!("dataset-access-test-{}", std::process::id()));
+    let access = Access::from_file(&write_access_file(
+        &dir,
+        "[datasets]\ntumor = [\"alice\", \"pipeline\"]\nnormal = [\"*\"]\n",
+    ))
+    .unwrap();
+
+    assert!(access.may_access("alice", "tumor"));
+    assert!(access.may_access("pipeline", "tumor"));
+    assert!(!access.may_access("bob", "tumor"));
+    assert!(access.may_access("bob", "normal"));
+    // Datasets without a rule are hidden when there is no "*" rule
+    assert!(!access.may_access("alice", "default"));
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn htpasswd_without_bcrypt_test() {
+    let dir = std::env::temp_dir().join(format!("htpasswd-test-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("users.htpasswd");
+    fs::write(&path, "alice:{SHA}W6ph5Mm5Pz8GgiULbPgzG37mj9g=\n").unwrap();
+
+    assert!(read_htpasswd(&path).is_err());
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn user_guard_test() {
+    let dir = std::env::temp_dir().join(format!("user-guard-test-{}", std::process::id()));
+    let client = client(&write_access_file(
+        &dir,
+        &format!("[datasets]\n{} = [\"alice\"]\n", DEFAULT_DATASET),
+    ));
+    let reference = format!("/api/v1/{}/reference/chr1/0/10", DEFAULT_DATASET);
+
+    // Without credentials browsers are asked for a user name and password
+    let response = client.get("/api/v1/datasets").dispatch();
+    assert_eq!(response.status(), Status::Unauthorized);
+    assert!(response
+        .headers()
+        .get_one("WWW-Authenticate")
+        .unwrap()
+        .starts_with("Basic"));
+
+    let response = client
+        .get("/api/v1/datasets")
+        .header(Header::new("Authorization", basic("alice", "bob-password")))
+        .dispatch();
+    assert_eq!(response.status(), Status::Unauthorized);
+
+    let response = client
+        .get(reference.clone())
+        .header(Header::new(
+            "Authorization",
+            basic("alice", "alice-password"),
+        ))
+        .dispatch();
+    assert_eq!(response.status(), Status::Ok);
+
+    // Datasets of other users are denied and not listed
+    let response = client
+        .get(reference.clone())
+        .header(Header::new("Authorization", basic("bob", "bob-password")))
+        .dispatch();
+    assert_eq!(response.status(), Status::Forbidden);
+
+    let mut response = client
+        .get("/api/v1/datasets")
+        .header(Header::new("Authorization", basic("bob", "bob-password")))
+        .dispatch();
+    assert_eq!(response.status(), Status::Ok);
+    assert_eq!(response.body_string(), Some(String::from("[]")));
+
+    fs::remove_dir_all(&dir).unwrap();
+}
@@ -1,8 +1,18 @@
 extern crate rust_htslib;
 
-use fasta_reader::read_fasta;
-use rust_htslib::bam::record::CigarStringView;
+use allele_support::Allele;
+use aux_tags::{aux_bytes, aux_string};
+use fasta_reader::{read_fasta, Nucleobase};
+use grouping::{samples_by_read_group, GroupSeparator, ReadInfo, ReadInfos, ReadOrder};
+use layout::compact_rows;
+use modifications::{parse_modifications, BaseModification};
+use pairs::{classify_pair, InsertSizeRange, PairClass};
+use read_details::{parse_supplementary, reference_length, SupplementaryAlignment};
+use region::UnknownChromosome;
+use rust_htslib::bam::record::{Cigar, CigarStringView};
 use rust_htslib::{bam, bam::Read};
+use std::collections::HashMap;
+use std::error::Error;
 use std::fmt;
 use std::path::Path;
 
@@ -13,17 +23,69 @@ pub enum Marker {
     C,
     G,
     N,
+    // IUPAC ambiguity codes
+    R,
+    Y,
+    S,
+    W,
+    K,
+    M,
+    B,
+    D,
+    H,
+    V,
     Deletion,
     Insertion,
     Match,
     Pairing,
+    SoftClip,
+    Split,
+    SplitEnd,
+    Modification,
+}
+
+impl Marker {
+    /// Whether the marker is an IUPAC code for more than one base, like N.
+    pub fn is_ambiguous(&self) -> bool {
+        match self {
+            Marker::N
+            | Marker::R
+            | Marker::Y
+            | Marker::S
+            | Marker::W
+            | Marker::K
+            | Marker::M
+            | Marker::B
+            | Marker::D
+            | Marker::H
+            | Marker::V => true,
+            _ => false,
+        }
+    }
+}
+
+/// Strand a read is aligned to, from flag 0x10.
+#[derive(Serialize, Clone, Copy, Debug, PartialEq)]
+pub enum Strand {
+    Forward,
+    Reverse,
+}
+
+impl Strand {
+    pub fn of(record: &bam::Record) -> Strand {
+        if record.is_reverse() {
+            Strand::Reverse
+        } else {
+            Strand::Forward
+        }
+    }
 }
 
 #[derive(Clone, Debug)]
 pub struct Alignment {
     sequence: String,
     pos: i64,
-    length: u16,
+    length: u32,
     flags: Vec<u16>,
     name: String,
     cigar: CigarStringView,
@@ -31,6 +93,24 @@ pub struct Alignment {
     mate_pos: i64,
     tid: i32,
     mate_tid: i32,
+    chrom: String,
+    // End of the alignment on the reference, from the CIGAR
+    end_pos: i64,
+    // End of the mate from its MC tag or its record, if either is available
+    mate_end: Option<i64>,
+    insert_size: i64,
+    pair_class: PairClass,
+    strand: Strand,
+    info: ReadInfo,
+    // Start and end including soft clipped bases, which are shown as overhangs
+    clipped_start: i64,
+    clipped_end: i64,
+    // Other segments of a chimeric read, from its SA tag
+    supplementary: Vec<SupplementaryAlignment>,
+    // Modified bases from the MM and ML tags
+    modifications: Vec<BaseModification>,
+    // Allele the read supports at the variants of the region
+    allele: Option<Allele>,
 }
 
 #[derive(Serialize, Clone, Debug, PartialEq)]
@@ -43,6 +123,24 @@ pub struct AlignmentNucleobase {
     pub name: String,
     pub read_start: u32,
     pub read_end: u32,
+    pub row: u32,
+    pub pair_class: PairClass,
+    pub strand: Strand,
+    // Probability of a modified base, only set for the `Modification` marker
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub probability: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub allele: Option<Allele>,
+}
+
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct Coverage {
+    pub start_position: f64,
+    pub end_position: f64,
+    pub depth: u32,
+    // Reads on each strand, to spot a strand bias
+    pub forward: u32,
+    pub reverse: u32,
 }
 
 #[derive(Serialize, Clone, Debug, PartialEq)]
@@ -54,6 +152,57 @@ pub struct AlignmentMatch {
     pub name: String,
     pub read_start: u32,
     pub read_end: u32,
+    pub row: u32,
+    pub pair_class: PairClass,
+    pub strand: Strand,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub allele: Option<Allele>,
+}
+
+impl Alignment {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Start and end of the aligned bases on the reference, without soft clips.
+    pub fn span(&self) -> (i64, i64) {
+        (self.pos, self.end_pos)
+    }
+
+    /// The read bases aligned to the reference positions `from..to`, including the bases that
+    /// are inserted between them.
+    pub fn aligned_bases(&self, from: i64, to: i64) -> Vec<u8> {
+        let sequence = self.sequence.as_bytes();
+        let mut bases = Vec::new();
+        let mut read_index = 0;
+        let mut ref_pos = self.pos;
+
+        for c in self.cigar.iter() {
+            match c {
+                Cigar::Match(l) | Cigar::Equal(l) | Cigar::Diff(l) => {
+                    for _ in 0..*l {
+                        if ref_pos >= from && ref_pos < to {
+                            bases.push(sequence[read_index]);
+                        }
+                        read_index += 1;
+                        ref_pos += 1;
+                    }
+                }
+                Cigar::Ins(l) => {
+                    let length = *l as usize;
+                    if from < ref_pos && ref_pos < to {
+                        bases.extend_from_slice(&sequence[read_index..read_index + length]);
+                    }
+                    read_index += length;
+                }
+                Cigar::SoftClip(l) => read_index += *l as usize,
+                Cigar::Del(l) | Cigar::RefSkip(l) => ref_pos += *l as i64,
+                Cigar::HardClip(_) | Cigar::Pad(_) => {}
+            }
+        }
+
+        bases
+    }
 }
 
 impl fmt::Display for Alignment {
@@ -92,26 +241,83 @@ pub fn decode_flags(code: u16) -> Vec<u16> {
     read_map
 }
 
-pub fn read_indexed_bam(path: &Path, chrom: String, from: u64, to: u64) -> Vec<Alignment> {
-    let mut bam = bam::IndexedReader::from_path(&path).unwrap();
-    let tid = bam.header().tid(chrom.as_bytes()).unwrap();
+pub fn read_indexed_bam(
+    path: &Path,
+    chrom: String,
+    from: u64,
+    to: u64,
+) -> Result<Vec<Alignment>, Box<dyn Error>> {
+    let mut bam = bam::IndexedReader::from_path(&path)?;
+
+    fetch_alignments(&mut bam, chrom, from, to)
+}
+
+pub fn fetch_alignments(
+    bam: &mut bam::IndexedReader,
+    chrom: String,
+    from: u64,
+    to: u64,
+) -> Result<Vec<Alignment>, Box<dyn Error>> {
+    let tid = match bam.header().tid(chrom.as_bytes()) {
+        Some(tid) => tid,
+        None => return Err(UnknownChromosome(chrom).into()),
+    };
+    let header = bam.header().clone();
+    let samples = samples_by_read_group(&header);
 
     let mut alignments: Vec<Alignment> = Vec::new();
 
-    bam.fetch(tid, from, to).unwrap();
+    bam.fetch(tid, from, to)?;
 
     for r in bam.records() {
-        let rec = r.unwrap();
+        let rec = r?;
 
-        let a = make_alignment(rec);
+        let a = make_alignment(rec, &header, &samples);
 
         alignments.push(a);
     }
 
+    // Without an MC tag the end of a mate is only known when it was fetched as well
+    let ends: HashMap<(String, i32, i64), i64> = alignments
+        .iter()
+        .map(|a| ((a.name.clone(), a.tid, a.pos), a.end_pos))
+        .collect();
+    for a in alignments.iter_mut() {
+        if a.mate_end.is_none() {
+            a.mate_end = ends.get(&(a.name.clone(), a.mate_tid, a.mate_pos)).cloned();
+        }
+    }
+
+    Ok(alignments)
+}
+
+/// Marks forward-reverse pairs whose insert size is outside of the expected range.
+pub fn classify_insert_sizes(alignments: &mut [Alignment], insert_sizes: &InsertSizeRange) {
+    for a in alignments.iter_mut() {
+        a.pair_class = insert_sizes.classify(a.pair_class, a.insert_size);
+    }
+}
+
+/// Sets the allele that every read supports, by read name.
+pub fn set_alleles(alignments: &mut [Alignment], alleles: &HashMap<String, Allele>) {
+    for a in alignments.iter_mut() {
+        a.allele = alleles.get(&a.name).cloned();
+    }
+}
+
+/// The grouping attributes of every read, by read name.
+pub fn read_infos(alignments: &[Alignment]) -> ReadInfos {
     alignments
+        .iter()
+        .map(|a| (a.name.clone(), a.info.clone()))
+        .collect()
 }
 
-fn make_alignment(record: bam::Record) -> Alignment {
+fn make_alignment(
+    record: bam::Record,
+    header: &bam::HeaderView,
+    samples: &HashMap<String, String>,
+) -> Alignment {
     let has_pair = record.is_paired();
 
     let mate_pos = record.mpos();
@@ -121,12 +327,36 @@ fn make_alignment(record: bam::Record) -> Alignment {
 
     //Cigar String
     let cigstring = record.cigar();
+    let end_pos = cigstring.end_pos();
+
+    let info = ReadInfo::new(&record, header, samples);
+
+    let mate_end = aux_string(&record, b"MC")
+        .and_then(|mate_cigar| reference_length(&mate_cigar))
+        .map(|length| mate_pos + length as i64);
+    // Older files use the draft tags Mm and Ml. The skip counts of hard clipped reads refer to
+    // bases that are not stored, so their modifications can't be placed.
+    let hard_clipped = cigstring.leading_hardclips() > 0 || cigstring.trailing_hardclips() > 0;
+    let modifications = match aux_string(&record, b"MM").or_else(|| aux_string(&record, b"Mm")) {
+        Some(ref mm) if !hard_clipped => {
+            let ml = aux_bytes(&record, b"ML")
+                .or_else(|| aux_bytes(&record, b"Ml"))
+                .unwrap_or_default();
+            parse_modifications(mm, &ml, &record.seq().as_bytes(), record.is_reverse())
+        }
+        _ => Vec::new(),
+    };
+    let supplementary = aux_string(&record, b"SA")
+        .map(|sa| parse_supplementary(&sa))
+        .unwrap_or_default();
 
     //Position
     let pos = record.pos();
+    let clipped_start = pos - cigstring.leading_softclips();
+    let clipped_end = end_pos + cigstring.trailing_softclips();
 
     //Länge
-    let le = record.seq().len() as u16;
+    let le = record.seq().len() as u32;
 
     //Sequenz
     let seq = record.seq().as_bytes();
@@ -151,258 +381,249 @@ fn make_alignment(record: bam::Record) -> Alignment {
         mate_pos: mate_pos,
         tid: tid,
         mate_tid: mtid,
+        chrom: String::from_utf8_lossy(header.tid2name(tid as u32)).into_owned(),
+        end_pos: end_pos,
+        mate_end: mate_end,
+        insert_size: record.insert_size(),
+        clipped_start: clipped_start,
+        clipped_end: clipped_end,
+        supplementary: supplementary,
+        modifications: modifications,
+        pair_class: classify_pair(&record),
+        strand: Strand::of(&record),
+        info: info,
+        allele: None,
     };
 
     read
 }
 
+/// Attributes that are shared by all markers of a read, computed once per read.
+struct ReadMarkers<'a> {
+    alignment: &'a Alignment,
+    read_start: u32,
+    read_end: u32,
+}
+
+impl<'a> ReadMarkers<'a> {
+    fn new(alignment: &'a Alignment) -> ReadMarkers<'a> {
+        let (read_start, read_end) = fragment_bounds(alignment);
+
+        ReadMarkers {
+            alignment: alignment,
+            read_start: read_start as u32,
+            read_end: read_end as u32,
+        }
+    }
+
+    fn base(
+        &self,
+        marker_type: Marker,
+        bases: String,
+        start_position: f64,
+        end_position: f64,
+    ) -> AlignmentNucleobase {
+        AlignmentNucleobase {
+            marker_type: marker_type,
+            bases: bases,
+            start_position: start_position,
+            end_position: end_position,
+            flags: self.alignment.flags.clone(),
+            name: self.alignment.name.clone(),
+            read_start: self.read_start,
+            read_end: self.read_end,
+            row: 0,
+            pair_class: self.alignment.pair_class,
+            strand: self.alignment.strand,
+            probability: None,
+            allele: self.alignment.allele,
+        }
+    }
+
+    fn span(&self, marker_type: Marker, start_position: f64, end_position: f64) -> AlignmentMatch {
+        AlignmentMatch {
+            marker_type: marker_type,
+            start_position: start_position,
+            end_position: end_position,
+            flags: self.alignment.flags.clone(),
+            name: self.alignment.name.clone(),
+            read_start: self.read_start,
+            read_end: self.read_end,
+            row: 0,
+            pair_class: self.alignment.pair_class,
+            strand: self.alignment.strand,
+            allele: self.alignment.allele,
+        }
+    }
+}
+
+/// Turns the reads into markers for the region in a single pass over each CIGAR. Bases that match
+/// the reference are merged into one `Match` per run, deletions into one `Deletion` per run.
 pub fn make_nucleobases(
-    fasta_path: &Path,
-    chrom: String,
+    ref_bases: &[Nucleobase],
     snippets: Vec<Alignment>,
     from: u64,
     to: u64,
 ) -> (Vec<AlignmentNucleobase>, Vec<AlignmentMatch>) {
     let mut bases: Vec<AlignmentNucleobase> = Vec::new();
     let mut matches: Vec<AlignmentMatch> = Vec::new();
+    let (from, to) = (from as i64, to as i64);
+
+    for s in snippets.iter() {
+        let read = ReadMarkers::new(s);
+        let sequence = s.sequence.as_bytes();
+
+        if s.paired && s.end_pos < s.mate_pos && s.tid == s.mate_tid {
+            matches.push(read.span(
+                Marker::Pairing,
+                s.end_pos as f64 - 0.5,
+                s.mate_pos as f64 - 0.5,
+            ));
+        }
 
-    let ref_bases = read_fasta(fasta_path, chrom, from, to);
-
-    for s in snippets {
-        let mut cigar_offset: i64 = 0;
-        let mut read_offset: i64 = 0;
-        let base_string = s.sequence.clone();
-        let char_vec: Vec<char> = base_string.chars().collect();
-
-        let mut soft_clip_begin = true;
-
-        let p = s.clone();
-
-        if p.paired && (p.pos + p.length as i64) < p.mate_pos && p.tid == p.mate_tid {
-            let pairing = AlignmentMatch {
-                marker_type: Marker::Pairing,
-                start_position: (p.pos + p.length as i64) as f64 - 0.5,
-                end_position: p.mate_pos as f64 - 0.5,
-                flags: p.flags.clone(),
-                name: p.name.clone(),
-                read_start: p.pos.clone() as u32,
-                read_end: (p.mate_pos.clone() + 100) as u32,
+        if !s.supplementary.is_empty() {
+            // The read continues at its other segments from its longer clipped end
+            let leading = s.cigar.leading_softclips() + s.cigar.leading_hardclips();
+            let trailing = s.cigar.trailing_softclips() + s.cigar.trailing_hardclips();
+            let breakpoint = if leading >= trailing {
+                s.pos
+            } else {
+                s.end_pos
             };
+            if breakpoint >= from && breakpoint <= to {
+                bases.push(read.base(
+                    Marker::SplitEnd,
+                    split_partners(&s.supplementary),
+                    breakpoint as f64 - 1.0,
+                    breakpoint as f64,
+                ));
+            }
 
-            matches.push(pairing);
+            // Like mates, segments on the same chromosome are connected by the left one
+            for sa in s.supplementary.iter() {
+                if sa.region.chromosome == s.chrom && sa.region.from as i64 >= s.end_pos {
+                    matches.push(read.span(
+                        Marker::Split,
+                        s.end_pos as f64 - 0.5,
+                        sa.region.from as f64 - 0.5,
+                    ));
+                }
+            }
         }
 
-        for c in s.cigar.iter() {
-            let mut match_count = 0;
-            let mut match_start = 0;
-            let mut match_ending = false;
+        if !s.modifications.is_empty() {
+            bases.extend(modified_bases(&read, from, to));
+        }
+
+        // Position of the next base of the read and the reference
+        let mut read_index: usize = 0;
+        let mut ref_pos = s.pos;
+        // No bases were aligned yet, so soft clipped bases belong to a leading clip
+        let mut leading_clip = true;
 
+        for c in s.cigar.iter() {
             match c {
-                rust_htslib::bam::record::Cigar::Match(c) => {
-                    for _i in 0..rust_htslib::bam::record::Cigar::Match(*c).len() {
-                        let snip = s.clone();
-                        let b = char_vec[cigar_offset as usize];
-
-                        if snip.pos + read_offset >= from as i64
-                            && snip.pos + read_offset < to as i64
-                        {
-                            let ref_index = snip.pos + read_offset - from as i64;
-                            let ref_base = &ref_bases[ref_index as usize];
-
-                            if ref_base.get_marker_type() == b {
-                                // Create long rule while bases match
-                                if match_count == 0 {
-                                    match_start = snip.pos as i64 + read_offset;
-                                }
-                                match_count += 1;
-                                match_ending = true;
+                Cigar::Match(l) | Cigar::Equal(l) | Cigar::Diff(l) => {
+                    // Start of the current run of bases that match the reference
+                    let mut match_start = None;
+
+                    for _ in 0..*l {
+                        if ref_pos >= from && ref_pos < to {
+                            let b = sequence[read_index] as char;
+                            let ref_base = &ref_bases[(ref_pos - from) as usize];
 
-                            //m = Marker::Match; // Match with reference fasta
+                            if matches_reference(b, ref_base.get_marker_type()) {
+                                match_start = match_start.or(Some(ref_pos));
                             } else {
-                                let (mtch, base) = make_markers(
-                                    snip.clone(),
-                                    b,
-                                    read_offset,
-                                    match_start,
-                                    match_count,
-                                );
-                                match mtch {
-                                    Some(m) => matches.push(m),
-                                    _ => {}
+                                if let Some(start) = match_start.take() {
+                                    matches.push(read.span(
+                                        Marker::Match,
+                                        start as f64 - 0.5,
+                                        ref_pos as f64 - 0.5,
+                                    ));
                                 }
-                                bases.push(base);
-
-                                match_count = 0;
-                                match_start = 0;
-
-                                match_ending = false;
+                                bases.push(read.base(
+                                    base_marker(b),
+                                    b.to_string(),
+                                    ref_pos as f64 - 0.5,
+                                    ref_pos as f64 + 0.5,
+                                ));
                             }
                         }
-                        cigar_offset += 1;
-                        read_offset += 1;
+                        read_index += 1;
+                        ref_pos += 1;
                     }
 
-                    if match_ending {
-                        let mtch = end_mismatch_detection(s.clone(), match_start, match_count);
-                        matches.push(mtch);
+                    if let Some(start) = match_start {
+                        let end = ref_pos.min(to);
+                        matches.push(read.span(
+                            Marker::Match,
+                            start as f64 - 0.5,
+                            end as f64 - 0.5,
+                        ));
                     }
 
-                    soft_clip_begin = false;
+                    leading_clip = false;
                 }
-                rust_htslib::bam::record::Cigar::Ins(c) => {
-                    let snip = s.clone();
-                    let p: f64 = snip.pos as f64 + read_offset as f64 - 0.5;
-                    let m: Marker = Marker::Insertion;
-                    let rs;
-                    let re;
-
-                    let mut b = String::from("");
-                    for _i in 0..rust_htslib::bam::record::Cigar::Ins(*c).len() {
-                        let char = char_vec[cigar_offset as usize];
-                        b.push(char);
-
-                        cigar_offset += 1;
-                    }
-
-                    if snip.paired && snip.tid == snip.mate_tid {
-                        if snip.pos < snip.mate_pos {
-                            re = snip.mate_pos + 100;
-                            rs = snip.pos;
-                        } else {
-                            rs = snip.mate_pos;
-                            re = snip.pos + snip.length as i64;
-                        }
-                    } else {
-                        rs = snip.pos;
-                        re = snip.pos + snip.length as i64;
+                Cigar::Ins(l) => {
+                    let length = *l as usize;
+                    // Drawn around the boundary between the previous and the next reference base
+                    if from < ref_pos && ref_pos <= to {
+                        let inserted = &sequence[read_index..read_index + length];
+                        bases.push(read.base(
+                            Marker::Insertion,
+                            String::from_utf8_lossy(inserted).into_owned(),
+                            ref_pos as f64 - 1.0,
+                            ref_pos as f64,
+                        ));
                     }
+                    read_index += length;
 
-                    let base = AlignmentNucleobase {
-                        marker_type: m,
-                        bases: b,
-                        start_position: p.clone() as f64 - 0.5,
-                        end_position: p as f64 + 0.5,
-                        flags: snip.flags,
-                        name: snip.name,
-                        read_start: rs as u32,
-                        read_end: re as u32,
-                    };
-
-                    if from as f64 <= (base.start_position + 0.5)
-                        && (base.start_position + 0.5) <= to as f64
-                    {
-                        bases.push(base);
-                    }
-
-                    soft_clip_begin = false;
+                    leading_clip = false;
                 }
-                rust_htslib::bam::record::Cigar::Del(c) => {
-                    for _i in 0..rust_htslib::bam::record::Cigar::Del(*c).len() {
-                        let snip = s.clone();
-                        let m = Marker::Deletion;
-                        let p = snip.pos as i64 + read_offset;
-                        let f = snip.flags;
-                        let n = snip.name;
-                        let rs;
-                        let re;
-                        let b = String::from("");
-
-                        if snip.paired && snip.tid == snip.mate_tid {
-                            if snip.pos < snip.mate_pos {
-                                re = snip.mate_pos + 100;
-                                rs = snip.pos;
-                            } else {
-                                rs = snip.mate_pos;
-                                re = snip.pos + snip.length as i64;
-                            }
-                        } else {
-                            rs = snip.pos;
-                            re = snip.pos + snip.length as i64;
-                        }
-
-                        let base = AlignmentNucleobase {
-                            marker_type: m,
-                            bases: b,
-                            start_position: p.clone() as f64 - 0.5,
-                            end_position: p as f64 + 0.5,
-                            flags: f,
-                            name: n,
-                            read_start: rs as u32,
-                            read_end: re as u32,
-                        };
-
-                        read_offset += 1;
-
-                        if from as f64 <= (base.start_position + 0.5)
-                            && (base.start_position + 0.5) <= to as f64
-                        {
-                            bases.push(base);
-                        }
+                Cigar::Del(l) => {
+                    let length = *l as i64;
+                    if ref_pos < to && ref_pos + length > from {
+                        bases.push(read.base(
+                            Marker::Deletion,
+                            String::new(),
+                            ref_pos as f64 - 0.5,
+                            (ref_pos + length) as f64 - 0.5,
+                        ));
                     }
+                    ref_pos += length;
 
-                    soft_clip_begin = false;
+                    leading_clip = false;
                 }
-                rust_htslib::bam::record::Cigar::SoftClip(c) => {
-                    for _i in 0..rust_htslib::bam::record::Cigar::SoftClip(*c).len() {
-                        let snip = s.clone();
-                        let b = char_vec[cigar_offset as usize];
-
-                        if snip.pos + read_offset >= from as i64
-                            && snip.pos + read_offset < to as i64
-                        {
-                            let ref_index = snip.pos + read_offset - from as i64;
-                            let ref_base = &ref_bases[ref_index as usize];
-
-                            if ref_base.get_marker_type() == b {
-                                // Create long rule while bases match
-                                if match_count == 0 {
-                                    match_start = snip.pos as i64 + read_offset;
-                                }
-                                match_count += 1;
-                                match_ending = true;
-                            } else {
-                                let (mtch, base) = make_markers(
-                                    snip.clone(),
-                                    b,
-                                    read_offset,
-                                    match_start,
-                                    match_count,
-                                );
-                                match mtch {
-                                    Some(m) => matches.push(m),
-                                    _ => {}
-                                }
-                                bases.push(base);
-
-                                match_count = 0;
-                                match_start = 0;
-
-                                match_ending = false;
-                            }
-                        }
+                Cigar::RefSkip(l) => {
+                    ref_pos += *l as i64;
 
-                        cigar_offset += 1;
-                        if !soft_clip_begin {
-                            read_offset += 1;
-                        }
-                    }
+                    leading_clip = false;
+                }
+                Cigar::SoftClip(l) => {
+                    let length = *l as usize;
 
-                    if match_ending {
-                        let mtch = end_mismatch_detection(s.clone(), match_start, match_count);
-                        matches.push(mtch);
-                    }
+                    // Leading clips hang over the start of the alignment, trailing clips over its
+                    // end. They don't consume reference bases.
+                    let start = if leading_clip {
+                        s.pos - length as i64
+                    } else {
+                        ref_pos
+                    };
 
-                    soft_clip_begin = false;
-                }
-                rust_htslib::bam::record::Cigar::HardClip(c) => {
-                    for _i in 0..rust_htslib::bam::record::Cigar::HardClip(*c).len() {
-                        cigar_offset += 1;
+                    if start < to && start + length as i64 > from {
+                        let clipped = &sequence[read_index..read_index + length];
+                        bases.push(read.base(
+                            Marker::SoftClip,
+                            String::from_utf8_lossy(clipped).into_owned(),
+                            start as f64 - 0.5,
+                            (start + length as i64) as f64 - 0.5,
+                        ));
                     }
-
-                    soft_clip_begin = false;
+                    read_index += length;
                 }
-                _ => {
-                    soft_clip_begin = false;
+                Cigar::HardClip(_) | Cigar::Pad(_) => {
+                    // Hard clipped bases are not part of the sequence
                 }
             }
         }
@@ -410,104 +631,196 @@ pub fn make_nucleobases(
     (bases, matches)
 }
 
-fn make_markers(
-    snip: Alignment,
-    b: char,
-    read_offset: i64,
-    match_start: i64,
-    match_count: i64,
-) -> (Option<AlignmentMatch>, AlignmentNucleobase) {
-    let m: Marker;
-
-    match b {
-        // Mismatch
-        'A' => m = Marker::A,
-        'T' => m = Marker::T,
-        'C' => m = Marker::C,
-        'N' => m = Marker::N,
-        'G' => m = Marker::G,
-        _ => m = Marker::Deletion,
+/// The modified bases of a read that are aligned to the region.
+fn modified_bases(read: &ReadMarkers, from: i64, to: i64) -> Vec<AlignmentNucleobase> {
+    let snip = read.alignment;
+    let mut calls: HashMap<usize, Vec<&BaseModification>> = HashMap::new();
+    for m in snip.modifications.iter() {
+        calls.entry(m.read_index).or_insert_with(Vec::new).push(m);
     }
 
-    let p = snip.pos as i64 + read_offset;
-    let f = snip.flags;
-    let n = snip.name;
-
-    let rs: i64;
-    let re: i64;
-
-    if snip.paired && snip.tid == snip.mate_tid {
-        if snip.pos < snip.mate_pos {
-            re = snip.mate_pos + 100;
-            rs = snip.pos;
-        } else {
-            rs = snip.mate_pos;
-            re = snip.pos + snip.length as i64;
+    let mut bases = Vec::new();
+    let mut read_index = 0;
+    let mut ref_pos = snip.pos;
+    for c in snip.cigar.iter() {
+        match c {
+            Cigar::Match(l) | Cigar::Equal(l) | Cigar::Diff(l) => {
+                for _ in 0..*l {
+                    if ref_pos >= from && ref_pos < to {
+                        for m in calls.get(&read_index).into_iter().flatten() {
+                            let mut base = read.base(
+                                Marker::Modification,
+                                m.modification.clone(),
+                                ref_pos as f64 - 0.5,
+                                ref_pos as f64 + 0.5,
+                            );
+                            base.probability = Some(m.probability);
+                            bases.push(base);
+                        }
+                    }
+                    read_index += 1;
+                    ref_pos += 1;
+                }
+            }
+            Cigar::Ins(l) | Cigar::SoftClip(l) => read_index += *l as usize,
+            Cigar::Del(l) | Cigar::RefSkip(l) => ref_pos += *l as i64,
+            Cigar::HardClip(_) | Cigar::Pad(_) => {}
         }
-    } else {
-        rs = snip.pos;
-        re = snip.pos + snip.length as i64;
     }
 
-    let mut mtch = None;
-
-    if match_count > 0 {
-        // First mismatch detection must lead to new creation of all previous matches
-        mtch = Some(AlignmentMatch {
-            marker_type: Marker::Match,
-            start_position: match_start as f64 - 0.5,
-            end_position: (match_start + match_count - 1) as f64 + 0.5,
-            flags: f.clone(),
-            name: n.clone(),
-            read_start: rs.clone() as u32,
-            read_end: re.clone() as u32,
-        });
-    }
+    bases
+}
 
-    let base = AlignmentNucleobase {
-        marker_type: m,
-        bases: b.to_string(),
-        start_position: p.clone() as f64 - 0.5,
-        end_position: p as f64 + 0.5,
-        flags: f,
-        name: n,
-        read_start: rs as u32,
-        read_end: re as u32,
-    };
-    (mtch, base)
+/// Removes the soft clipped overhangs of the reads, e.g. when they are switched off in the viewer.
+pub fn hide_soft_clips(bases: &mut Vec<AlignmentNucleobase>) {
+    bases.retain(|b| b.marker_type != Marker::SoftClip);
 }
 
-fn end_mismatch_detection(snip: Alignment, match_start: i64, match_count: i64) -> AlignmentMatch {
-    let f = snip.flags;
-    let n = snip.name;
+/// Hides insertions and deletions shorter than `min_length` bases, which are mostly sequencing
+/// errors in long reads. Reads are drawn as matching the reference across hidden deletions.
+pub fn hide_small_indels(
+    bases: &mut Vec<AlignmentNucleobase>,
+    matches: &mut Vec<AlignmentMatch>,
+    min_length: u32,
+) {
+    let min_length = min_length as f64;
+    let mut kept = Vec::with_capacity(bases.len());
+
+    for b in bases.drain(..) {
+        match b.marker_type {
+            Marker::Insertion if (b.bases.len() as f64) < min_length => {}
+            Marker::Deletion if b.end_position - b.start_position < min_length => {
+                matches.push(AlignmentMatch {
+                    marker_type: Marker::Match,
+                    start_position: b.start_position,
+                    end_position: b.end_position,
+                    flags: b.flags,
+                    name: b.name,
+                    read_start: b.read_start,
+                    read_end: b.read_end,
+                    row: b.row,
+                    pair_class: b.pair_class,
+                    strand: b.strand,
+                    allele: b.allele,
+                });
+            }
+            _ => kept.push(b),
+        }
+    }
+
+    *bases = kept;
+}
 
-    let rs: i64;
-    let re: i64;
+/// Keeps only the reads that support the alternative allele of a variant in the region. The
+/// emptied rows of a grouped or sorted layout are removed. Rows of the shared layout of the
+/// default order are kept, so that reads stay in their row between requests.
+pub fn keep_alt_reads(
+    bases: &mut Vec<AlignmentNucleobase>,
+    matches: &mut Vec<AlignmentMatch>,
+    groups: &mut Vec<GroupSeparator>,
+    order: &ReadOrder,
+) {
+    bases.retain(|b| b.allele == Some(Allele::Alt));
+    matches.retain(|m| m.allele == Some(Allele::Alt));
+
+    if order.is_default() {
+        return;
+    }
+    compact_rows(
+        bases
+            .iter_mut()
+            .map(|b| &mut b.row)
+            .chain(matches.iter_mut().map(|m| &mut m.row))
+            .collect(),
+        groups,
+    );
+}
 
-    if snip.paired && snip.tid == snip.mate_tid {
+/// Start of the read and end of its mate on the reference for reads with a mate on the same
+/// chromosome, otherwise start and end of the read. Soft clips of the read and other segments
+/// of a split read on the same chromosome are included.
+fn fragment_bounds(snip: &Alignment) -> (i64, i64) {
+    let bounds = if snip.paired && snip.tid == snip.mate_tid {
         if snip.pos < snip.mate_pos {
-            re = snip.mate_pos + 100;
-            rs = snip.pos;
+            // Without the mate's alignment, it is assumed to cover as many bases as the read
+            let mate_end = snip.mate_end.unwrap_or(snip.mate_pos + snip.length as i64);
+            (snip.clipped_start.max(0), mate_end)
         } else {
-            rs = snip.mate_pos;
-            re = snip.pos + snip.length as i64;
+            (snip.mate_pos, snip.clipped_end)
         }
     } else {
-        rs = snip.pos;
-        re = snip.pos + snip.length as i64;
+        (snip.clipped_start.max(0), snip.clipped_end)
+    };
+
+    snip.supplementary
+        .iter()
+        .filter(|sa| sa.region.chromosome == snip.chrom)
+        .fold(bounds, |(start, end), sa| {
+            (
+                start.min(sa.region.from as i64),
+                end.max(sa.region.to as i64),
+            )
+        })
+}
+
+/// Loci of the other segments of a split read, e.g. `chr2:1001-1070 (-)`.
+fn split_partners(supplementary: &[SupplementaryAlignment]) -> String {
+    supplementary
+        .iter()
+        .map(|sa| format!("{} ({})", sa.region, if sa.reverse { '-' } else { '+' }))
+        .collect::<Vec<String>>()
+        .join(", ")
+}
+
+fn base_marker(b: char) -> Marker {
+    match b.to_ascii_uppercase() {
+        'A' => Marker::A,
+        'T' => Marker::T,
+        'C' => Marker::C,
+        'G' => Marker::G,
+        'R' => Marker::R,
+        'Y' => Marker::Y,
+        'S' => Marker::S,
+        'W' => Marker::W,
+        'K' => Marker::K,
+        'M' => Marker::M,
+        'B' => Marker::B,
+        'D' => Marker::D,
+        'H' => Marker::H,
+        'V' => Marker::V,
+        _ => Marker::N,
     }
+}
 
-    let mtch = AlignmentMatch {
-        marker_type: Marker::Match,
-        start_position: match_start as f64 - 0.5,
-        end_position: (match_start + match_count - 1) as f64 + 0.5,
-        flags: f.clone(),
-        name: n.clone(),
-        read_start: rs.clone() as u32,
-        read_end: re.clone() as u32,
-    };
+/// Bases an IUPAC code of the reference stands for.
+fn iupac_bases(code: char) -> &'static str {
+    match code {
+        'A' => "A",
+        'C' => "C",
+        'G' => "G",
+        'T' | 'U' => "T",
+        'R' => "AG",
+        'Y' => "CT",
+        'S' => "CG",
+        'W' => "AT",
+        'K' => "GT",
+        'M' => "AC",
+        'B' => "CGT",
+        'D' => "AGT",
+        'H' => "ACT",
+        'V' => "ACG",
+        'N' => "ACGT",
+        _ => "",
+    }
+}
 
-    mtch
+/// Whether a read base matches the reference, ignoring the case of soft-masked reference bases.
+/// Bases that an ambiguity code of the reference stands for are matches, too.
+fn matches_reference(base: char, reference: char) -> bool {
+    let base = base.to_ascii_uppercase();
+    let reference = reference.to_ascii_uppercase();
+
+    base == reference || (base != 'N' && iupac_bases(reference).contains(base))
 }
 
 pub fn get_reads(
@@ -516,9 +829,53 @@ pub fn get_reads(
     chrom: String,
     from: u64,
     to: u64,
-) -> (Vec<AlignmentNucleobase>, Vec<AlignmentMatch>) {
-    let alignments = read_indexed_bam(path, chrom.clone(), from, to);
-    let bases = make_nucleobases(fasta_path, chrom, alignments, from, to);
+) -> Result<(Vec<AlignmentNucleobase>, Vec<AlignmentMatch>), Box<dyn Error>> {
+    let alignments = read_indexed_bam(path, chrom.clone(), from, to)?;
+    let ref_bases = read_fasta(fasta_path, chrom, from, to)?;
 
-    bases
+    Ok(make_nucleobases(&ref_bases, alignments, from, to))
+}
+
+/// Read depth at every position of the region, including positions without any reads, in total
+/// and per strand.
+pub fn fetch_coverage(
+    bam: &mut bam::IndexedReader,
+    chrom: String,
+    from: u64,
+    to: u64,
+) -> Result<Vec<Coverage>, Box<dyn Error>> {
+    let tid = match bam.header().tid(chrom.as_bytes()) {
+        Some(tid) => tid,
+        None => return Err(UnknownChromosome(chrom).into()),
+    };
+
+    let mut coverage: Vec<Coverage> = (from..to)
+        .map(|pos| Coverage {
+            start_position: pos as f64 - 0.5,
+            end_position: pos as f64 + 0.5,
+            depth: 0,
+            forward: 0,
+            reverse: 0,
+        })
+        .collect();
+
+    bam.fetch(tid, from, to)?;
+
+    for p in bam.pileup() {
+        let pileup = p?;
+        let pos = pileup.pos() as u64;
+
+        if pos >= from && pos < to {
+            let c = &mut coverage[(pos - from) as usize];
+            c.depth = pileup.depth();
+            for a in pileup.alignments() {
+                match Strand::of(&a.record()) {
+                    Strand::Forward => c.forward += 1,
+                    Strand::Reverse => c.reverse += 1,
+                }
+            }
+        }
+    }
+
+    Ok(coverage)
 }
@@ -1,5 +1,15 @@
 use super::*;
-use alignment_reader::Marker;
+use alignment_reader::{
+    fetch_coverage, get_reads, hide_small_indels, hide_soft_clips, Marker, Strand,
+};
+use columnar::ColumnarAlignments;
+use fasta_reader::read_fasta;
+use grouping::ReadInfos;
+use layout::{Layouts, RowLayout};
+use modifications::{methylation_levels, parse_modifications, ModificationType};
+use pairs::PairClass;
+use read_details::{parse_supplementary, reference_length};
+use rust_htslib::bam;
 use std::path::Path;
 
 #[test]
@@ -10,7 +20,8 @@ fn match_test() {
         String::from("chr1"),
         0,
         100,
-    );
+    )
+    .unwrap();
     matches.retain(|m| m.marker_type == Marker::Match);
 
     let mut compare_matches = Vec::new();
@@ -23,6 +34,10 @@ fn match_test() {
         name: "sim_Som1-5-2_chr1_1_1acd6f".to_string(),
         read_start: 4,
         read_end: 789364,
+        row: 0,
+        pair_class: PairClass::Normal,
+        strand: Strand::Forward,
+        allele: None,
     };
 
     compare_matches.push(m1);
@@ -35,6 +50,10 @@ fn match_test() {
         name: "sim_Som1-5-2_chr1_1_1acd6f".to_string(),
         read_start: 4,
         read_end: 789364,
+        row: 0,
+        pair_class: PairClass::Normal,
+        strand: Strand::Forward,
+        allele: None,
     };
 
     compare_matches.push(m2);
@@ -50,7 +69,8 @@ fn mismatch_test() {
         String::from("chr1"),
         0,
         110,
-    );
+    )
+    .unwrap();
     bam.retain(|m| m.marker_type == Marker::T);
 
     let mut compare_bam = Vec::new();
@@ -64,6 +84,11 @@ fn mismatch_test() {
         name: "sim_Som1-5-2_chr1_1_1acd6f".to_string(),
         read_start: 4,
         read_end: 789364,
+        row: 0,
+        pair_class: PairClass::Normal,
+        strand: Strand::Forward,
+        probability: None,
+        allele: None,
     };
 
     compare_bam.push(m);
@@ -79,7 +104,8 @@ fn insertion_test() {
         String::from("chr1"),
         0,
         100,
-    );
+    )
+    .unwrap();
     bam.retain(|m| m.marker_type == Marker::Insertion);
 
     let mut compare_bam = Vec::new();
@@ -93,6 +119,11 @@ fn insertion_test() {
         name: "sim_Som1-5-2_chr1_1_1acd6f".to_string(),
         read_start: 4,
         read_end: 789364,
+        row: 0,
+        pair_class: PairClass::Normal,
+        strand: Strand::Forward,
+        probability: None,
+        allele: None,
     };
 
     compare_bam.push(m);
@@ -108,7 +139,8 @@ fn deletion_test() {
         String::from("chr1"),
         0,
         100,
-    );
+    )
+    .unwrap();
     bam.retain(|m| m.marker_type == Marker::Deletion);
 
     let mut compare_bam = Vec::new();
@@ -121,10 +153,524 @@ fn deletion_test() {
         flags: vec![2, 32, 64],
         name: "sim_Som1-5-2_chr1_1_1acd6f".to_string(),
         read_start: 4,
-        read_end: 103,
+        read_end: 102,
+        row: 0,
+        pair_class: PairClass::Unpaired,
+        strand: Strand::Forward,
+        probability: None,
+        allele: None,
     };
 
     compare_bam.push(m);
 
     assert_eq!(compare_bam, bam);
 }
+
+#[test]
+fn coverage_test() {
+    let mut bam = bam::IndexedReader::from_path(Path::new("tests/resources/test.bam")).unwrap();
+    let coverage = fetch_coverage(&mut bam, String::from("chr1"), 2, 6).unwrap();
+
+    let depths: Vec<u32> = coverage.iter().map(|c| c.depth).collect();
+    assert_eq!(depths, vec![0, 0, 1, 1]);
+    assert_eq!(coverage[0].start_position, 1.5);
+    assert_eq!(coverage[3].end_position, 5.5);
+
+    // Both mates of pairs.bam overlap each other, the left ones on the forward strand
+    let mut bam = bam::IndexedReader::from_path(Path::new("tests/resources/pairs.bam")).unwrap();
+    let coverage = fetch_coverage(&mut bam, String::from("chr1"), 25, 80).unwrap();
+    assert_eq!((coverage[5].forward, coverage[5].reverse), (2, 0));
+    assert_eq!((coverage[50].forward, coverage[50].reverse), (0, 2));
+}
+
+#[test]
+fn row_layout_test() {
+    let mut layout = RowLayout::new();
+
+    let (mut bases, mut matches) = get_reads(
+        Path::new("tests/resources/test.bam"),
+        Path::new("tests/resources/ref.fa"),
+        String::from("chr1"),
+        0,
+        50,
+    )
+    .unwrap();
+    layout.assign(&mut bases, &mut matches);
+    assert!(matches.iter().all(|m| m.row > 0));
+
+    let (mut next_bases, mut next_matches) = get_reads(
+        Path::new("tests/resources/test.bam"),
+        Path::new("tests/resources/ref.fa"),
+        String::from("chr1"),
+        50,
+        100,
+    )
+    .unwrap();
+    layout.assign(&mut next_bases, &mut next_matches);
+
+    // Reads that are visible in both windows keep their row
+    for m in next_matches.iter() {
+        if let Some(previous) = matches.iter().find(|p| p.name == m.name) {
+            assert_eq!(previous.row, m.row);
+        }
+    }
+
+    // Reads in the same row do not overlap
+    for a in next_matches.iter().chain(matches.iter()) {
+        for b in next_matches.iter().chain(matches.iter()) {
+            if a.name != b.name && a.row == b.row {
+                assert!(a.read_end < b.read_start || b.read_end < a.read_start);
+            }
+        }
+    }
+}
+
+#[test]
+fn row_packing_test() {
+    let read = |name: &str, read_start: u32, read_end: u32| AlignmentMatch {
+        marker_type: Marker::Match,
+        start_position: read_start as f64 - 0.5,
+        end_position: read_end as f64 - 0.5,
+        flags: vec![],
+        name: name.to_string(),
+        read_start: read_start,
+        read_end: read_end,
+        row: 0,
+        pair_class: PairClass::Normal,
+        strand: Strand::Forward,
+        allele: None,
+    };
+    let mut layout = RowLayout::new();
+
+    let mut matches = vec![read("c", 21, 40), read("a", 10, 20), read("b", 15, 30)];
+    layout.assign(&mut [], &mut matches);
+    let rows: Vec<u32> = matches.iter().map(|m| m.row).collect();
+    assert_eq!(rows, vec![1, 1, 2]);
+
+    // A read left of the previous window fits into the first row again
+    let mut matches = vec![read("d", 2, 9), read("a", 10, 20)];
+    layout.assign(&mut [], &mut matches);
+    let rows: Vec<u32> = matches.iter().map(|m| m.row).collect();
+    assert_eq!(rows, vec![1, 1]);
+}
+
+#[test]
+fn row_layout_limit_test() {
+    let read = |name: &str, read_start: u32, read_end: u32| AlignmentMatch {
+        marker_type: Marker::Match,
+        start_position: read_start as f64 - 0.5,
+        end_position: read_end as f64 - 0.5,
+        flags: vec![],
+        name: name.to_string(),
+        read_start: read_start,
+        read_end: read_end,
+        row: 0,
+        pair_class: PairClass::Normal,
+        strand: Strand::Forward,
+        allele: None,
+    };
+    let mut layout = RowLayout::new().with_max_reads(2);
+
+    let mut matches = vec![read("a", 10, 20), read("b", 15, 30), read("c", 25, 40)];
+    layout.assign(&mut [], &mut matches);
+    let rows: Vec<u32> = matches.iter().map(|m| m.row).collect();
+    assert_eq!(rows, vec![1, 2, 1]);
+
+    // Only the reads of the last window are remembered, and they keep their rows
+    let mut matches = vec![read("c", 25, 40), read("d", 45, 50)];
+    layout.assign(&mut [], &mut matches);
+    let rows: Vec<u32> = matches.iter().map(|m| m.row).collect();
+    assert_eq!(rows, vec![1, 1]);
+
+    // The rows of forgotten reads are free again
+    let mut matches = vec![read("b", 15, 22), read("e", 5, 12)];
+    layout.assign(&mut [], &mut matches);
+    let rows: Vec<u32> = matches.iter().map(|m| m.row).collect();
+    assert_eq!(rows, vec![1, 1]);
+}
+
+#[test]
+fn row_extent_change_test() {
+    let read = |name: &str, read_start: u32, read_end: u32| AlignmentMatch {
+        marker_type: Marker::Match,
+        start_position: read_start as f64 - 0.5,
+        end_position: read_end as f64 - 0.5,
+        flags: vec![],
+        name: name.to_string(),
+        read_start: read_start,
+        read_end: read_end,
+        row: 0,
+        pair_class: PairClass::Normal,
+        strand: Strand::Forward,
+        allele: None,
+    };
+    let mut layout = RowLayout::new();
+
+    let mut matches = vec![read("a", 10, 20), read("b", 25, 40)];
+    layout.assign(&mut [], &mut matches);
+    let rows: Vec<u32> = matches.iter().map(|m| m.row).collect();
+    assert_eq!(rows, vec![1, 1]);
+
+    // A read that became longer, e.g. because its mate was fetched, keeps its row if it fits
+    let mut matches = vec![read("a", 10, 22), read("b", 25, 40)];
+    layout.assign(&mut [], &mut matches);
+    let rows: Vec<u32> = matches.iter().map(|m| m.row).collect();
+    assert_eq!(rows, vec![1, 1]);
+
+    // Otherwise it moves, instead of overlapping its neighbour
+    let mut matches = vec![read("a", 10, 30), read("b", 25, 40)];
+    layout.assign(&mut [], &mut matches);
+    let rows: Vec<u32> = matches.iter().map(|m| m.row).collect();
+    assert_eq!(rows, vec![2, 1]);
+
+    // Its old interval is free again
+    let mut matches = vec![read("c", 5, 21)];
+    layout.assign(&mut [], &mut matches);
+    assert_eq!(matches[0].row, 1);
+}
+
+#[test]
+fn session_layouts_test() {
+    let read = |name: &str, read_start: u32, read_end: u32| AlignmentMatch {
+        marker_type: Marker::Match,
+        start_position: read_start as f64 - 0.5,
+        end_position: read_end as f64 - 0.5,
+        flags: vec![],
+        name: name.to_string(),
+        read_start: read_start,
+        read_end: read_end,
+        row: 0,
+        pair_class: PairClass::Normal,
+        strand: Strand::Forward,
+        allele: None,
+    };
+    let layouts = Layouts::new(4);
+
+    let mut matches = vec![read("a", 10, 20)];
+    layouts.assign("first", "chr1", &mut [], &mut matches);
+    assert_eq!(matches[0].row, 1);
+
+    // Another viewer does not see the reads placed for the first one
+    let mut matches = vec![read("b", 15, 30)];
+    layouts.assign("second", "chr1", &mut [], &mut matches);
+    assert_eq!(matches[0].row, 1);
+
+    let mut matches = vec![read("b", 15, 30)];
+    layouts.assign("first", "chr1", &mut [], &mut matches);
+    assert_eq!(matches[0].row, 2);
+
+    // Without a session, only the requested reads are laid out
+    let mut matches = vec![read("b", 15, 30)];
+    layouts.assign_ordered(
+        None,
+        "chr1",
+        &mut [],
+        &mut matches,
+        &ReadInfos::new(),
+        &ReadOrder::default(),
+    );
+    assert_eq!(matches[0].row, 1);
+}
+
+#[test]
+fn columnar_test() {
+    let (bases, matches) = get_reads(
+        Path::new("tests/resources/test.bam"),
+        Path::new("tests/resources/ref.fa"),
+        String::from("chr1"),
+        0,
+        100,
+    )
+    .unwrap();
+    let columns = ColumnarAlignments::new(&bases, &matches);
+
+    assert_eq!(columns.reads.name, vec!["sim_Som1-5-2_chr1_1_1acd6f"]);
+    assert_eq!(columns.reads.flags, vec![1 | 2 | 32 | 64]);
+    assert_eq!(columns.events.read.len(), bases.len() + matches.len());
+    assert_eq!(columns.events.marker_type[0], matches[0].marker_type);
+    assert_eq!(columns.events.start_position[0], matches[0].start_position);
+}
+
+#[test]
+fn read_details_test() {
+    let mut bam = bam::IndexedReader::from_path(Path::new("tests/resources/test.bam")).unwrap();
+    let details = fetch_read_details(
+        &mut bam,
+        String::from("chr1"),
+        0,
+        20,
+        "sim_Som1-5-2_chr1_1_1acd6f",
+    )
+    .unwrap();
+
+    assert_eq!(details.len(), 1);
+    let read = &details[0];
+    assert_eq!(read.flags, vec![1, 2, 32, 64]);
+    assert_eq!(read.region, Region::new(String::from("chr1"), 4, 102));
+    assert_eq!(read.cigar, "16M2I82M");
+    assert_eq!(read.mapq, 30);
+    assert_eq!(read.insert_size, 315);
+    assert_eq!(
+        read.mate,
+        Some(Region::new(String::from("chr1"), 789264, 789265))
+    );
+    assert_eq!(read.sequence.len(), 100);
+    assert_eq!(read.qualities.len(), 100);
+    assert_eq!(read.tags["NM"], json!(3));
+    assert_eq!(read.tags["RG"], json!("1"));
+    assert!(read.supplementary.is_empty());
+
+    let other = fetch_read_details(&mut bam, String::from("chr1"), 0, 20, "unknown").unwrap();
+    assert!(other.is_empty());
+
+    let unknown = fetch_read_details(&mut bam, String::from("chrZ"), 0, 20, "unknown");
+    assert!(unknown.err().unwrap().is::<UnknownChromosome>());
+}
+
+#[test]
+fn supplementary_test() {
+    assert_eq!(reference_length("10S40M2D5I50M3H"), Some(92));
+    assert_eq!(reference_length("40M2"), None);
+
+    let alignments = parse_supplementary("chr2,1001,-,30S70M,60,2;chr5,20,+,70M30S,13,0;");
+    assert_eq!(alignments.len(), 2);
+    assert_eq!(
+        alignments[0].region,
+        Region::new(String::from("chr2"), 1000, 1070)
+    );
+    assert!(alignments[0].reverse);
+    assert_eq!(alignments[0].mapq, 60);
+    assert_eq!(alignments[0].edit_distance, 2);
+    assert!(!alignments[1].reverse);
+}
+
+#[test]
+fn mate_end_test() {
+    let (bases, matches) = get_reads(
+        Path::new("tests/resources/pairs.bam"),
+        Path::new("tests/resources/ref.fa"),
+        String::from("chr1"),
+        0,
+        123,
+    )
+    .unwrap();
+
+    // pair_mc has MC tags, the mates of pair_record are found among the fetched records
+    let bounds = |name: &str| {
+        if name == "pair_mc" {
+            (10, 110)
+        } else {
+            (20, 85)
+        }
+    };
+    for m in matches.iter() {
+        assert_eq!((m.read_start, m.read_end), bounds(&m.name));
+    }
+    for b in bases.iter() {
+        assert_eq!((b.read_start, b.read_end), bounds(&b.name));
+    }
+
+    let pairings: Vec<(f64, f64)> = matches
+        .iter()
+        .filter(|m| m.marker_type == Marker::Pairing)
+        .map(|m| (m.start_position, m.end_position))
+        .collect();
+    assert_eq!(pairings, vec![(37.5, 69.5), (49.5, 59.5)]);
+}
+
+#[test]
+fn soft_clip_test() {
+    let (mut bases, _matches) = get_reads(
+        Path::new("tests/resources/pairs.bam"),
+        Path::new("tests/resources/ref.fa"),
+        String::from("chr1"),
+        0,
+        123,
+    )
+    .unwrap();
+
+    // The leading clip of 5S25M hangs over the alignment start at 60
+    let clips: Vec<&AlignmentNucleobase> = bases
+        .iter()
+        .filter(|b| b.marker_type == Marker::SoftClip)
+        .collect();
+    assert_eq!(clips.len(), 1);
+    assert_eq!(clips[0].name, "pair_record");
+    assert_eq!(clips[0].bases, "NNNNN");
+    assert_eq!(
+        (clips[0].start_position, clips[0].end_position),
+        (54.5, 59.5)
+    );
+
+    hide_soft_clips(&mut bases);
+    assert!(bases.iter().all(|b| b.marker_type != Marker::SoftClip));
+}
+
+#[test]
+fn split_read_test() {
+    let (bases, matches) = get_reads(
+        Path::new("tests/resources/split.bam"),
+        Path::new("tests/resources/ref.fa"),
+        String::from("chr1"),
+        0,
+        123,
+    )
+    .unwrap();
+
+    // Primary and supplementary segment of split_read point to each other at their clipped ends
+    let split_ends: Vec<(&str, f64, &str)> = bases
+        .iter()
+        .filter(|b| b.marker_type == Marker::SplitEnd)
+        .map(|b| (&b.name[..], b.start_position, &b.bases[..]))
+        .collect();
+    assert_eq!(
+        split_ends,
+        vec![
+            ("split_read", 39.0, "chr1:71-90 (+)"),
+            ("translocation", 39.0, "chr2:501-515 (-)"),
+            ("split_read", 69.0, "chr1:11-40 (+)"),
+        ]
+    );
+
+    // Only the segments on the same chromosome are connected
+    let connectors: Vec<(f64, f64)> = matches
+        .iter()
+        .filter(|m| m.marker_type == Marker::Split)
+        .map(|m| (m.start_position, m.end_position))
+        .collect();
+    assert_eq!(connectors, vec![(39.5, 69.5)]);
+
+    // Both segments span the whole read, so they end up in the same row
+    for m in matches.iter().filter(|m| m.name == "split_read") {
+        assert_eq!((m.read_start, m.read_end), (10, 90));
+    }
+}
+
+#[test]
+fn modification_test() {
+    let modifications = parse_modifications("C+m,0,1;A+a?,0;", &[255, 0, 128], b"ACGCGC", false);
+    let calls: Vec<(usize, &str, f64)> = modifications
+        .iter()
+        .map(|m| (m.read_index, &m.modification[..], m.probability))
+        .collect();
+    assert_eq!(
+        calls,
+        vec![
+            (1, "5mC", 0.998046875),
+            (5, "5mC", 0.001953125),
+            (0, "6mA", 0.501953125),
+        ]
+    );
+
+    // Skips of reverse reads count the Gs of the stored sequence from its end
+    let modifications = parse_modifications("C+m,0;", &[255], b"GCGTA", true);
+    assert_eq!(modifications[0].read_index, 2);
+
+    let levels = methylation_levels(
+        vec![(9.5, 0.9), (9.5, 0.2), (12.5, 0.8)],
+        ModificationType::FiveMethylCytosine,
+    );
+    let levels: Vec<(f64, u32, u32)> = levels
+        .iter()
+        .map(|l| (l.start_position, l.calls, l.modified))
+        .collect();
+    assert_eq!(levels, vec![(9.5, 2, 1), (12.5, 1, 1)]);
+}
+
+#[test]
+fn modification_tags_test() {
+    let (bases, _matches) = get_reads(
+        Path::new("tests/resources/modifications.bam"),
+        Path::new("tests/resources/ref.fa"),
+        String::from("chr1"),
+        0,
+        20,
+    )
+    .unwrap();
+    let calls: Vec<(&str, f64, Option<f64>)> = bases
+        .iter()
+        .filter(|b| b.marker_type == Marker::Modification)
+        .map(|b| (&b.name[..], b.start_position, b.probability))
+        .collect();
+
+    // The draft tags Mm and Ml are read like MM and ML, hard clipped reads are left out
+    assert_eq!(
+        calls,
+        vec![
+            ("plain", 2.5, Some(0.998046875)),
+            ("draft", 3.5, Some(0.783203125)),
+        ]
+    );
+}
+
+#[test]
+fn small_indel_test() {
+    let (mut bases, mut matches) = get_reads(
+        Path::new("tests/resources/pairs.bam"),
+        Path::new("tests/resources/ref.fa"),
+        String::from("chr1"),
+        0,
+        123,
+    )
+    .unwrap();
+
+    // The 10 bases deleted by 15M10D15M at 70 are one span instead of one marker per base
+    let deletions: Vec<(f64, f64)> = bases
+        .iter()
+        .filter(|b| b.marker_type == Marker::Deletion)
+        .map(|b| (b.start_position, b.end_position))
+        .collect();
+    assert_eq!(deletions, vec![(84.5, 94.5)]);
+
+    // Only the insertion of 10M2I18M is shorter than 5 bases
+    hide_small_indels(&mut bases, &mut matches, 5);
+    assert!(bases.iter().all(|b| b.marker_type != Marker::Insertion));
+    assert!(bases.iter().any(|b| b.marker_type == Marker::Deletion));
+
+    // The read is drawn as a match across the hidden deletion
+    hide_small_indels(&mut bases, &mut matches, 11);
+    assert!(bases.iter().all(|b| b.marker_type != Marker::Deletion));
+    assert!(matches.iter().any(|m| m.name == "pair_mc"
+        && m.marker_type == Marker::Match
+        && (m.start_position, m.end_position) == (84.5, 94.5)));
+}
+
+#[test]
+fn masked_reference_test() {
+    let mismatches = |fasta: &str| -> Vec<(Marker, f64)> {
+        let (bases, _matches) = get_reads(
+            Path::new("tests/resources/test.bam"),
+            Path::new(fasta),
+            String::from("chr1"),
+            0,
+            100,
+        )
+        .unwrap();
+        bases
+            .into_iter()
+            .filter(|b| b.marker_type != Marker::Insertion)
+            .map(|b| (b.marker_type, b.start_position))
+            .collect()
+    };
+
+    // masked.fa is ref.fa with 20-39 in lower case, the C at 50 turned into Y (C or T) and the
+    // A at 60 into S (C or G). Only the read's A at 60 becomes a mismatch.
+    assert_eq!(mismatches("tests/resources/ref.fa"), vec![]);
+    assert_eq!(
+        mismatches("tests/resources/masked.fa"),
+        vec![(Marker::A, 59.5)]
+    );
+
+    let reference = read_fasta(
+        Path::new("tests/resources/masked.fa"),
+        String::from("chr1"),
+        19,
+        21,
+    )
+    .unwrap();
+    let reference: Vec<(char, bool)> = reference
+        .iter()
+        .map(|r| (r.marker_type, r.soft_masked))
+        .collect();
+    assert_eq!(reference, vec![('G', false), ('G', true)]);
+}
@@ -0,0 +1,219 @@
+use alignment_reader::{fetch_alignments, set_alleles, Alignment};
+use bio::alignment::distance::levenshtein;
+use bio::io::fasta;
+use fasta_reader::{fetch_padded_fasta, Nucleobase};
+use readers::Readers;
+use rust_htslib::bam;
+use std::collections::HashMap;
+use std::error::Error;
+use std::fs::File;
+use variant_reader::{fetch_alleles, VariantAlleles};
+
+// Reference bases on both sides of a variant that the reads are compared over
+const FLANK: i64 = 20;
+
+/// The allele of a variant that a read supports.
+#[derive(Serialize, Clone, Copy, Debug, PartialEq)]
+pub enum Allele {
+    Ref,
+    Alt,
+    Ambiguous,
+}
+
+/// Number of reads that support each allele of a variant.
+#[derive(Serialize, Clone, Copy, Debug, Default, PartialEq)]
+pub struct SupportCounts {
+    pub(crate) reference: u32,
+    pub(crate) alternative: u32,
+    pub(crate) ambiguous: u32,
+}
+
+impl SupportCounts {
+    pub fn new(alleles: &HashMap<String, Allele>) -> SupportCounts {
+        let mut counts = SupportCounts::default();
+
+        for allele in alleles.values() {
+            match allele {
+                Allele::Ref => counts.reference += 1,
+                Allele::Alt => counts.alternative += 1,
+                Allele::Ambiguous => counts.ambiguous += 1,
+            }
+        }
+
+        counts
+    }
+}
+
+/// Reference bases of a region, as upper case letters.
+pub struct ReferenceSequence {
+    start: i64,
+    bases: Vec<u8>,
+}
+
+impl ReferenceSequence {
+    pub fn new(nucleobases: &[Nucleobase]) -> ReferenceSequence {
+        ReferenceSequence {
+            start: nucleobases
+                .first()
+                .map_or(0, |b| (b.start_position + 0.5) as i64),
+            bases: nucleobases.iter().map(|b| b.marker_type as u8).collect(),
+        }
+    }
+
+    fn end(&self) -> i64 {
+        self.start + self.bases.len() as i64
+    }
+
+    /// The bases from `from` to `to`, as far as they are known.
+    fn get(&self, from: i64, to: i64) -> &[u8] {
+        let clip = |pos: i64| (pos - self.start).max(0).min(self.bases.len() as i64) as usize;
+
+        &self.bases[clip(from)..clip(to).max(clip(from))]
+    }
+}
+
+/// The allele of a variant that a read supports, or `None` if the read does not overlap the
+/// variant. The read bases around the variant are compared with the reference and with the
+/// alternative haplotype, and the read supports the one with the smaller edit distance. Reads
+/// that end within the variant or are as close to both haplotypes are ambiguous.
+pub fn read_allele(
+    read: &Alignment,
+    variant: &VariantAlleles,
+    reference: &ReferenceSequence,
+) -> Option<Allele> {
+    let (start, end) = read.span();
+    let pos = variant.position;
+    let ref_end = pos + variant.reference.len() as i64;
+
+    if start >= ref_end || end <= pos {
+        return None;
+    }
+
+    // Indels can only be told apart with at least one aligned base behind them
+    let margin = if variant.reference.len() == variant.alternative.len() {
+        0
+    } else {
+        1
+    };
+    if start > pos || end < ref_end + margin {
+        return Some(Allele::Ambiguous);
+    }
+
+    let from = (pos - FLANK).max(start).max(reference.start);
+    let to = (ref_end + FLANK).min(end).min(reference.end());
+    let haplotype = |allele: &str| {
+        [
+            reference.get(from, pos),
+            allele.as_bytes(),
+            reference.get(ref_end, to),
+        ]
+        .concat()
+        .to_ascii_uppercase()
+    };
+
+    let read_bases = read.aligned_bases(from, to).to_ascii_uppercase();
+    let ref_distance = levenshtein(&read_bases, &haplotype(&variant.reference));
+    let alt_distance = levenshtein(&read_bases, &haplotype(&variant.alternative));
+
+    Some(if alt_distance < ref_distance {
+        Allele::Alt
+    } else if ref_distance < alt_distance {
+        Allele::Ref
+    } else {
+        Allele::Ambiguous
+    })
+}
+
+/// The allele of a variant that every read overlapping it supports, by read name. If one mate is
+/// ambiguous, the other one decides. Mates that support different alleles are ambiguous.
+pub fn variant_support(
+    alignments: &[Alignment],
+    variant: &VariantAlleles,
+    reference: &ReferenceSequence,
+) -> HashMap<String, Allele> {
+    let mut alleles: HashMap<String, Allele> = HashMap::new();
+
+    for a in alignments {
+        if let Some(allele) = read_allele(a, variant, reference) {
+            let known = alleles.entry(a.name().to_owned()).or_insert(allele);
+            *known = match (*known, allele) {
+                (Allele::Ambiguous, allele) | (allele, Allele::Ambiguous) => allele,
+                (known, allele) if known == allele => known,
+                _ => Allele::Ambiguous,
+            };
+        }
+    }
+
+    alleles
+}
+
+/// Assigns the reads of a region to the alleles they support at the variants of the region. A
+/// read that supports the alternative allele of any variant is an ALT read, otherwise a read
+/// that supports a reference allele is a REF read.
+pub fn assign_alleles(
+    readers: &mut Readers,
+    alignments: &mut [Alignment],
+    chrom: &str,
+    from: u64,
+    to: u64,
+) -> Result<(), Box<dyn Error>> {
+    let variants = fetch_alleles(&mut readers.vcf, chrom, from, to)?;
+    if variants.is_empty() || alignments.is_empty() {
+        return Ok(());
+    }
+
+    let start = variants.iter().map(|v| v.position).min().unwrap();
+    let end = variants
+        .iter()
+        .map(|v| v.position + v.reference.len() as i64)
+        .max()
+        .unwrap();
+    let reference = ReferenceSequence::new(&fetch_padded_fasta(
+        &mut readers.fasta,
+        chrom.to_owned(),
+        start as u64,
+        end as u64,
+        FLANK as u64,
+    )?);
+
+    let mut reads: HashMap<String, Allele> = HashMap::new();
+    for v in variants.iter() {
+        for (name, allele) in variant_support(alignments, v, &reference) {
+            let summary = reads.entry(name).or_insert(allele);
+            *summary = match (*summary, allele) {
+                (Allele::Alt, _) | (_, Allele::Alt) => Allele::Alt,
+                (Allele::Ref, _) | (_, Allele::Ref) => Allele::Ref,
+                _ => Allele::Ambiguous,
+            };
+        }
+    }
+
+    set_alleles(alignments, &reads);
+
+    Ok(())
+}
+
+/// Counts the reads of a bam file that support each allele of a variant. The readers are
+/// passed in so that they can be reused for all variants of a report.
+pub fn count_support(
+    bam: &mut bam::IndexedReader,
+    fasta: &mut fasta::IndexedReader<File>,
+    chrom: String,
+    variant: &VariantAlleles,
+) -> Result<SupportCounts, Box<dyn Error>> {
+    let end = variant.position + variant.reference.len() as i64;
+    let alignments = fetch_alignments(bam, chrom.clone(), variant.position as u64, end as u64)?;
+    let reference = ReferenceSequence::new(&fetch_padded_fasta(
+        fasta,
+        chrom,
+        variant.position as u64,
+        end as u64,
+        FLANK as u64,
+    )?);
+
+    Ok(SupportCounts::new(&variant_support(
+        &alignments,
+        variant,
+        &reference,
+    )))
+}
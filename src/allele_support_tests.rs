@@ -0,0 +1,163 @@
+use super::*;
+use alignment_reader::{Marker, Strand};
+use allele_support::{count_support, Allele, SupportCounts};
+use bio::io::fasta;
+use grouping::GroupSeparator;
+use pairs::PairClass;
+use rust_htslib::bam;
+use variant_reader::{fetch_alleles, VariantAlleles};
+
+fn support(position: i64, reference: &str, alternative: &str) -> SupportCounts {
+    let variant =
+        VariantAlleles::new(position, reference.as_bytes(), alternative.as_bytes()).unwrap();
+    let mut bam = bam::IndexedReader::from_path(Path::new("tests/resources/test.bam")).unwrap();
+    let mut fasta = fasta::IndexedReader::from_file(&"tests/resources/ref.fa").unwrap();
+
+    count_support(&mut bam, &mut fasta, String::from("chr1"), &variant).unwrap()
+}
+
+fn counts(reference: u32, alternative: u32, ambiguous: u32) -> SupportCounts {
+    SupportCounts {
+        reference: reference,
+        alternative: alternative,
+        ambiguous: ambiguous,
+    }
+}
+
+#[test]
+fn snv_support_test() {
+    // The read matches the reference at both variants of the report test
+    let expected = SupportCounts {
+        reference: 1,
+        alternative: 0,
+        ambiguous: 0,
+    };
+    assert_eq!(support(4, "C", "T"), expected);
+    assert_eq!(support(28, "C", "T"), expected);
+}
+
+#[test]
+fn indel_support_test() {
+    // The read has the bases AA inserted after position 19
+    let expected = SupportCounts {
+        reference: 0,
+        alternative: 1,
+        ambiguous: 0,
+    };
+    assert_eq!(support(19, "G", "GAA"), expected);
+
+    // The read ends within the deleted bases
+    let expected = SupportCounts {
+        reference: 0,
+        alternative: 0,
+        ambiguous: 1,
+    };
+    assert_eq!(support(100, "GCC", "G"), expected);
+}
+
+#[test]
+fn vcf_support_test() {
+    let mut readers = Readers::open(
+        Path::new("tests/resources/ref.fa"),
+        Path::new("tests/resources/alleles.bam"),
+        Path::new("tests/resources/alleles.vcf.gz"),
+    )
+    .unwrap();
+    // The multi-allelic record at position 90 has one entry per alternative allele
+    let variants = fetch_alleles(&mut readers.vcf, "chr1", 0, 123).unwrap();
+    let alleles: Vec<(i64, &str, &str)> = variants
+        .iter()
+        .map(|v| (v.position, &v.reference[..], &v.alternative[..]))
+        .collect();
+    assert_eq!(
+        alleles,
+        vec![
+            (30, "A", "T"),
+            (50, "CA", "GT"),
+            (70, "ACTT", "A"),
+            (90, "G", "A"),
+            (90, "G", "C"),
+        ]
+    );
+
+    let support: Vec<SupportCounts> = variants
+        .iter()
+        .map(|v| {
+            count_support(
+                &mut readers.bam,
+                &mut readers.fasta,
+                String::from("chr1"),
+                v,
+            )
+            .unwrap()
+        })
+        .collect();
+    assert_eq!(
+        support,
+        vec![
+            // Two ALT reads, and a pair whose mates support different alleles
+            counts(1, 2, 1),
+            counts(1, 1, 0),
+            counts(1, 1, 0),
+            // Reads with the other alternative allele are as close to the reference
+            counts(1, 1, 2),
+            counts(1, 2, 1),
+        ]
+    );
+}
+
+#[test]
+fn symbolic_allele_test() {
+    assert_eq!(VariantAlleles::new(10, b"C", b"<DEL>"), None);
+    assert_eq!(VariantAlleles::new(10, b"C", b"*"), None);
+}
+
+#[test]
+fn alt_only_test() {
+    let read = |name: &str, row: u32, allele: Allele| AlignmentMatch {
+        marker_type: Marker::Match,
+        start_position: 9.5,
+        end_position: 19.5,
+        flags: vec![],
+        name: name.to_string(),
+        read_start: 10,
+        read_end: 20,
+        row: row,
+        pair_class: PairClass::Normal,
+        strand: Strand::Forward,
+        allele: Some(allele),
+    };
+    let separator = |group: &str, row: u32| GroupSeparator {
+        group: group.to_string(),
+        row: row,
+    };
+
+    let reads = vec![
+        read("a", 2, Allele::Ref),
+        read("b", 3, Allele::Alt),
+        read("c", 5, Allele::Ambiguous),
+    ];
+
+    let mut matches = reads.clone();
+    let mut groups = vec![separator("forward", 1), separator("reverse", 4)];
+    let order = ReadOrder {
+        group_by: Some(GroupBy::Strand),
+        sort_by_base: None,
+    };
+    keep_alt_reads(&mut Vec::new(), &mut matches, &mut groups, &order);
+
+    let rows: Vec<(&str, u32)> = matches.iter().map(|m| (&m.name[..], m.row)).collect();
+    assert_eq!(rows, vec![("b", 2)]);
+    assert_eq!(groups, vec![separator("forward", 1)]);
+
+    // Rows of the shared layout stay as they are
+    let mut matches = reads.clone();
+    keep_alt_reads(
+        &mut Vec::new(),
+        &mut matches,
+        &mut Vec::new(),
+        &ReadOrder::default(),
+    );
+    let rows: Vec<(&str, u32)> = matches.iter().map(|m| (&m.name[..], m.row)).collect();
+    assert_eq!(rows, vec![("b", 3)]);
+}
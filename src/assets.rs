@@ -0,0 +1,53 @@
+use std::borrow::Cow;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Web assets of the server that are compiled into the binary, so that it can be started from
+/// any working directory.
+const ASSETS: [(&str, &[u8]); 6] = [
+    ("index.html", include_bytes!("../static/index.html")),
+    ("vega.js", include_bytes!("../static/vega.js")),
+    ("style.css", include_bytes!("../static/style.css")),
+    ("vegaSpecs.json", include_bytes!("../static/vegaSpecs.json")),
+    (
+        "overviewSpecs.json",
+        include_bytes!("../static/overviewSpecs.json"),
+    ),
+    (
+        "optics/img/header.png",
+        include_bytes!("../static/optics/img/header.png"),
+    ),
+];
+
+pub fn embedded_asset(path: &str) -> Option<&'static [u8]> {
+    ASSETS
+        .iter()
+        .find(|(name, _)| *name == path)
+        .map(|(_, content)| *content)
+}
+
+/// Directory with web assets that replace the embedded ones.
+pub struct StaticDir(pub Option<PathBuf>);
+
+/// Reads a web asset from the directory given with `--static-dir`, falling back to the asset
+/// that is compiled into the binary.
+pub fn read_asset(static_dir: Option<&Path>, path: &Path) -> Option<Cow<'static, [u8]>> {
+    if let Some(static_dir) = static_dir {
+        if let Ok(content) = fs::read(static_dir.join(path)) {
+            return Some(Cow::Owned(content));
+        }
+    }
+
+    embedded_asset(path.to_str()?).map(Cow::Borrowed)
+}
+
+/// Brings a URL prefix like `genomes/` into the form `/genomes`, which can be put in front of
+/// absolute paths. An empty prefix stays empty.
+pub fn normalize_prefix(prefix: &str) -> String {
+    let trimmed = prefix.trim_matches('/');
+    if trimmed.is_empty() {
+        String::new()
+    } else {
+        format!("/{}", trimmed)
+    }
+}
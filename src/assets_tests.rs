@@ -0,0 +1,39 @@
+use super::*;
+use rocket::http::Status;
+use rocket::local::Client;
+
+fn client(prefix: &str) -> Client {
+    let base = if prefix.is_empty() { "/" } else { prefix };
+    let server = rocket::ignite()
+        .manage(None::<Access>)
+        .manage(StaticDir(None))
+        .mount(base, routes![home, static_asset]);
+    Client::new(server).unwrap()
+}
+
+#[test]
+fn normalize_prefix_test() {
+    assert_eq!(normalize_prefix(""), "");
+    assert_eq!(normalize_prefix("/"), "");
+    assert_eq!(normalize_prefix("genomes/"), "/genomes");
+    assert_eq!(normalize_prefix("/genomes"), "/genomes");
+}
+
+#[test]
+fn prefix_redirect_test() {
+    let client = client("/genomes");
+
+    let response = client.get("/genomes").dispatch();
+    assert_eq!(response.status(), Status::PermanentRedirect);
+    assert_eq!(response.headers().get_one("Location"), Some("/genomes/"));
+
+    let response = client.get("/genomes/").dispatch();
+    assert_eq!(response.status(), Status::Ok);
+    assert_eq!(response.content_type(), Some(ContentType::HTML));
+
+    let response = client.get("/genomes/vega.js").dispatch();
+    assert_eq!(response.status(), Status::Ok);
+
+    let response = client("").get("/").dispatch();
+    assert_eq!(response.status(), Status::Ok);
+}
@@ -0,0 +1,69 @@
+use access::Access;
+use rocket::http::Status;
+use rocket::request::{self, FromRequest, Request};
+use rocket::response::{self, Responder, Response};
+use rocket::{Outcome, State};
+use std::io::Cursor;
+
+/// Request guard for the user of a request. When the server runs with an access file, requests
+/// without valid credentials fail with 401, otherwise every request gets an anonymous user that
+/// may see all datasets.
+pub struct User<'r> {
+    name: Option<String>,
+    access: Option<&'r Access>,
+}
+
+impl<'r> User<'r> {
+    pub fn may_access(&self, dataset: &str) -> bool {
+        match (self.access, &self.name) {
+            (Some(access), Some(name)) => access.may_access(name, dataset),
+            _ => true,
+        }
+    }
+}
+
+impl<'a, 'r> FromRequest<'a, 'r> for User<'r> {
+    type Error = ();
+
+    fn from_request(request: &'a Request<'r>) -> request::Outcome<User<'r>, ()> {
+        let access = match request.guard::<State<Option<Access>>>()?.inner() {
+            Some(access) => access,
+            None => {
+                return Outcome::Success(User {
+                    name: None,
+                    access: None,
+                })
+            }
+        };
+
+        let name = request
+            .headers()
+            .get_one("Authorization")
+            .and_then(|authorization| access.authenticate(authorization));
+
+        match name {
+            Some(name) => Outcome::Success(User {
+                name: Some(name),
+                access: Some(access),
+            }),
+            None => Outcome::Failure((Status::Unauthorized, ())),
+        }
+    }
+}
+
+/// Response to requests without valid credentials, which makes browsers ask for a user name
+/// and password.
+pub struct Challenge;
+
+impl<'r> Responder<'r> for Challenge {
+    fn respond_to(self, _: &Request) -> response::Result<'r> {
+        Response::build()
+            .status(Status::Unauthorized)
+            .raw_header(
+                "WWW-Authenticate",
+                "Basic realm=\"genomes\", charset=\"UTF-8\"",
+            )
+            .sized_body(Cursor::new("authentication required"))
+            .ok()
+    }
+}
@@ -0,0 +1,72 @@
+use rust_htslib::bam;
+use rust_htslib::bam::record::Aux;
+use serde_json::Value;
+use std::collections::BTreeMap;
+
+/// Reads all optional fields of a record.
+pub fn aux_fields(record: &bam::Record) -> BTreeMap<String, Value> {
+    let mut fields = BTreeMap::new();
+
+    for field in record.aux_iter() {
+        match field {
+            Ok((tag, value)) => {
+                fields.insert(String::from_utf8_lossy(tag).into_owned(), aux_value(value));
+            }
+            // Truncated or unknown type, the rest of the data can't be split into fields
+            Err(_) => break,
+        }
+    }
+
+    fields
+}
+
+/// The value of a text tag like `MC` or `SA`.
+pub fn aux_string(record: &bam::Record, tag: &[u8]) -> Option<String> {
+    match record.aux(tag) {
+        Ok(Aux::String(value)) => Some(value.to_owned()),
+        _ => None,
+    }
+}
+
+/// The value of an integer tag like `HP` or `PS`, whatever its size.
+pub fn aux_integer(record: &bam::Record, tag: &[u8]) -> Option<i64> {
+    match record.aux(tag) {
+        Ok(Aux::I8(value)) => Some(value as i64),
+        Ok(Aux::U8(value)) => Some(value as i64),
+        Ok(Aux::I16(value)) => Some(value as i64),
+        Ok(Aux::U16(value)) => Some(value as i64),
+        Ok(Aux::I32(value)) => Some(value as i64),
+        Ok(Aux::U32(value)) => Some(value as i64),
+        _ => None,
+    }
+}
+
+/// The values of a byte array tag like `ML`.
+pub fn aux_bytes(record: &bam::Record, tag: &[u8]) -> Option<Vec<u8>> {
+    match record.aux(tag) {
+        Ok(Aux::ArrayU8(values)) => Some(values.iter().collect()),
+        _ => None,
+    }
+}
+
+fn aux_value(value: Aux) -> Value {
+    match value {
+        Aux::Char(c) => json!((c as char).to_string()),
+        Aux::I8(v) => json!(v),
+        Aux::U8(v) => json!(v),
+        Aux::I16(v) => json!(v),
+        Aux::U16(v) => json!(v),
+        Aux::I32(v) => json!(v),
+        Aux::U32(v) => json!(v),
+        Aux::Float(v) => json!(v),
+        Aux::Double(v) => json!(v),
+        Aux::String(v) | Aux::HexByteArray(v) => json!(v),
+        Aux::ArrayI8(a) => json!(a.iter().collect::<Vec<_>>()),
+        Aux::ArrayU8(a) => json!(a.iter().collect::<Vec<_>>()),
+        Aux::ArrayI16(a) => json!(a.iter().collect::<Vec<_>>()),
+        Aux::ArrayU16(a) => json!(a.iter().collect::<Vec<_>>()),
+        Aux::ArrayI32(a) => json!(a.iter().collect::<Vec<_>>()),
+        Aux::ArrayU32(a) => json!(a.iter().collect::<Vec<_>>()),
+        Aux::ArrayFloat(a) => json!(a.iter().collect::<Vec<_>>()),
+    }
+}
@@ -0,0 +1,176 @@
+use alignment_reader::{
+    classify_insert_sizes, fetch_alignments, fetch_coverage, make_nucleobases, read_infos,
+    AlignmentMatch, AlignmentNucleobase, Coverage,
+};
+use allele_support::assign_alleles;
+use fasta_reader::{fetch_fasta, Nucleobase};
+use grouping::ReadInfos;
+use lru::LruCache;
+use overview::{fetch_overview, Bin};
+use pairs::InsertSizeRange;
+use readers::ReaderPool;
+use region::Region;
+use std::error::Error;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use variant_reader::{fetch_variants, Variant};
+
+/// Least recently used cache for results computed for a region of a file.
+pub struct RegionCache<T> {
+    entries: Mutex<LruCache<(PathBuf, Region), T>>,
+}
+
+impl<T: Clone> RegionCache<T> {
+    pub fn new(capacity: usize) -> RegionCache<T> {
+        RegionCache {
+            entries: Mutex::new(LruCache::new(capacity)),
+        }
+    }
+
+    /// The cached value for a region, or the value computed by `f`. Errors are not cached.
+    pub fn get_or_try_insert_with<F, E>(&self, path: &Path, region: &Region, f: F) -> Result<T, E>
+    where
+        F: FnOnce() -> Result<T, E>,
+    {
+        let key = (path.to_owned(), region.clone());
+
+        if let Some(value) = self.entries.lock().unwrap().get(&key) {
+            return Ok(value.clone());
+        }
+
+        // The lock is not held while computing, so other requests are not blocked
+        let value = f()?;
+        self.entries.lock().unwrap().put(key, value.clone());
+
+        Ok(value)
+    }
+}
+
+pub struct Caches {
+    reference: RegionCache<Vec<Nucleobase>>,
+    alignments: RegionCache<(Vec<AlignmentNucleobase>, Vec<AlignmentMatch>, ReadInfos)>,
+    variants: RegionCache<Vec<Variant>>,
+    coverage: RegionCache<Vec<Coverage>>,
+    overview: RegionCache<Vec<Bin>>,
+    // Expected insert sizes of the bam file, which the cached alignments are classified with
+    insert_sizes: Option<InsertSizeRange>,
+}
+
+impl Caches {
+    pub fn new(capacity: usize, insert_sizes: Option<InsertSizeRange>) -> Caches {
+        Caches {
+            reference: RegionCache::new(capacity),
+            alignments: RegionCache::new(capacity),
+            variants: RegionCache::new(capacity),
+            coverage: RegionCache::new(capacity),
+            overview: RegionCache::new(capacity),
+            insert_sizes: insert_sizes,
+        }
+    }
+
+    pub fn reference(
+        &self,
+        pool: &ReaderPool,
+        region: &Region,
+    ) -> Result<Vec<Nucleobase>, Box<dyn Error>> {
+        self.reference
+            .get_or_try_insert_with(&pool.fasta_path, region, || {
+                let mut readers = pool.get()?;
+                fetch_fasta(
+                    &mut readers.fasta,
+                    region.chromosome.clone(),
+                    region.from,
+                    region.to,
+                )
+            })
+    }
+
+    /// The reads of a region, compared against the (cached) reference bases of the region and
+    /// assigned to the alleles of its variants, and the attributes the reads can be grouped by.
+    pub fn alignments(
+        &self,
+        pool: &ReaderPool,
+        region: &Region,
+    ) -> Result<(Vec<AlignmentNucleobase>, Vec<AlignmentMatch>, ReadInfos), Box<dyn Error>> {
+        self.alignments
+            .get_or_try_insert_with(&pool.bam_path, region, || {
+                let ref_bases = self.reference(pool, region)?;
+                let mut readers = pool.get()?;
+                let mut alignments = fetch_alignments(
+                    &mut readers.bam,
+                    region.chromosome.clone(),
+                    region.from,
+                    region.to,
+                )?;
+                if let Some(insert_sizes) = &self.insert_sizes {
+                    classify_insert_sizes(&mut alignments, insert_sizes);
+                }
+                assign_alleles(
+                    &mut readers,
+                    &mut alignments,
+                    &region.chromosome,
+                    region.from,
+                    region.to,
+                )?;
+                let infos = read_infos(&alignments);
+                let (bases, matches) =
+                    make_nucleobases(&ref_bases, alignments, region.from, region.to);
+                Ok((bases, matches, infos))
+            })
+    }
+
+    pub fn variants(
+        &self,
+        pool: &ReaderPool,
+        region: &Region,
+    ) -> Result<Vec<Variant>, Box<dyn Error>> {
+        self.variants
+            .get_or_try_insert_with(&pool.vcf_path, region, || {
+                let mut readers = pool.get()?;
+                fetch_variants(
+                    &mut readers.vcf,
+                    region.chromosome.clone(),
+                    region.from,
+                    region.to,
+                )
+            })
+    }
+
+    pub fn coverage(
+        &self,
+        pool: &ReaderPool,
+        region: &Region,
+    ) -> Result<Vec<Coverage>, Box<dyn Error>> {
+        self.coverage
+            .get_or_try_insert_with(&pool.bam_path, region, || {
+                let mut readers = pool.get()?;
+                fetch_coverage(
+                    &mut readers.bam,
+                    region.chromosome.clone(),
+                    region.from,
+                    region.to,
+                )
+            })
+    }
+
+    pub fn overview(
+        &self,
+        pool: &ReaderPool,
+        region: &Region,
+        bins: u64,
+    ) -> Result<Vec<Bin>, Box<dyn Error>> {
+        self.overview
+            .get_or_try_insert_with(&pool.bam_path, region, || {
+                let mut readers = pool.get()?;
+                let readers = &mut *readers;
+                fetch_overview(
+                    &mut readers.bam,
+                    &mut readers.vcf,
+                    region.chromosome.clone(),
+                    region.from,
+                    region.to,
+                    bins,
+                )
+            })
+    }
+}
@@ -0,0 +1,113 @@
+use super::*;
+use cache::{Caches, RegionCache};
+use readers::ReaderPool;
+use std::cell::Cell;
+use std::path::Path;
+
+fn test_pool() -> ReaderPool {
+    ReaderPool::new(
+        Path::new("tests/resources/ref.fa"),
+        Path::new("tests/resources/test.bam"),
+        Path::new("tests/resources/report-test.vcf.gz"),
+    )
+}
+
+#[test]
+fn cache_hit_test() {
+    let cache = RegionCache::new(2);
+    let path = Path::new("test.bam");
+    let region = Region::new(String::from("chr1"), 0, 10);
+    let calls = Cell::new(0);
+    let compute = || -> Result<u32, ()> {
+        calls.set(calls.get() + 1);
+        Ok(42)
+    };
+
+    assert_eq!(cache.get_or_try_insert_with(path, &region, compute), Ok(42));
+    assert_eq!(cache.get_or_try_insert_with(path, &region, compute), Ok(42));
+    assert_eq!(calls.get(), 1);
+
+    // Errors are not cached
+    let other = Region::new(String::from("chr1"), 10, 20);
+    assert_eq!(
+        cache.get_or_try_insert_with(path, &other, || Err(())),
+        Err(())
+    );
+    assert_eq!(cache.get_or_try_insert_with(path, &other, compute), Ok(42));
+    assert_eq!(calls.get(), 2);
+}
+
+#[test]
+fn cache_eviction_test() {
+    let cache = RegionCache::new(1);
+    let path = Path::new("test.bam");
+    let first = Region::new(String::from("chr1"), 0, 10);
+    let second = Region::new(String::from("chr1"), 10, 20);
+    let calls = Cell::new(0);
+    let compute = || -> Result<u32, ()> {
+        calls.set(calls.get() + 1);
+        Ok(calls.get())
+    };
+
+    assert_eq!(cache.get_or_try_insert_with(path, &first, compute), Ok(1));
+    assert_eq!(cache.get_or_try_insert_with(path, &second, compute), Ok(2));
+    // The first region was evicted to make room for the second one
+    assert_eq!(cache.get_or_try_insert_with(path, &first, compute), Ok(3));
+    // Regions of different files are cached separately
+    assert_eq!(
+        cache.get_or_try_insert_with(Path::new("other.bam"), &first, compute),
+        Ok(4)
+    );
+}
+
+#[test]
+fn reader_reuse_test() {
+    let pool = test_pool().with_max_idle(1);
+    assert_eq!(pool.idle_count(), 0);
+
+    {
+        let _first = pool.get().unwrap();
+        let _second = pool.get().unwrap();
+    }
+    // Only one of the two readers is kept open
+    assert_eq!(pool.idle_count(), 1);
+
+    {
+        let _readers = pool.get().unwrap();
+        assert_eq!(pool.idle_count(), 0);
+    }
+    assert_eq!(pool.idle_count(), 1);
+}
+
+#[test]
+fn reader_error_test() {
+    let pool = ReaderPool::new(
+        Path::new("tests/resources/ref.fa"),
+        Path::new("tests/resources/missing.bam"),
+        Path::new("tests/resources/report-test.vcf.gz"),
+    );
+    let caches = Caches::new(4, None);
+    let region = Region::new(String::from("chr1"), 0, 60);
+
+    assert!(caches.reference(&pool, &region).is_err());
+    assert!(caches.alignments(&pool, &region).is_err());
+    assert_eq!(caches.reference(&test_pool(), &region).unwrap().len(), 60);
+}
+
+#[test]
+fn unknown_chromosome_test() {
+    let pool = test_pool();
+    let caches = Caches::new(4, None);
+    let region = Region::new(String::from("chrZ"), 0, 100);
+
+    let unknown = |e: Option<Box<dyn Error>>| e.unwrap().is::<UnknownChromosome>();
+    assert!(unknown(caches.reference(&pool, &region).err()));
+    assert!(unknown(caches.alignments(&pool, &region).err()));
+    assert!(unknown(caches.variants(&pool, &region).err()));
+    assert!(unknown(caches.coverage(&pool, &region).err()));
+    assert!(unknown(caches.overview(&pool, &region, 10).err()));
+
+    // The server answers with 404 instead of failing
+    let error = fetch_error(caches.alignments(&pool, &region).err().unwrap());
+    assert_eq!(error.0, Status::NotFound);
+}
@@ -0,0 +1,291 @@
+use cache::Caches;
+use layout::Layouts;
+use name_index::NameIndex;
+use pairs::{InsertSizeRange, INSERT_SIZE_SAMPLE};
+use readers::ReaderPool;
+use region_data::Tracks;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fs;
+use std::hash::{Hash, Hasher};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, RwLock};
+use std::thread;
+
+/// Name of the dataset that is served by the routes without a dataset, and the name of the
+/// only dataset when the server is started with a single bam, fasta and vcf file.
+pub const DEFAULT_DATASET: &str = "default";
+
+/// One entry of the catalogue. Relative paths are resolved against the directory of the
+/// catalogue file.
+#[derive(Deserialize, Clone, Debug, PartialEq)]
+pub struct DatasetConfig {
+    pub(crate) reference: PathBuf,
+    pub(crate) alignments: PathBuf,
+    pub(crate) variants: PathBuf,
+    pub(crate) annotation: Option<PathBuf>,
+    pub(crate) description: Option<String>,
+    pub(crate) tracks: Option<Vec<String>>,
+    // Estimated from the first pairs of the bam file when not given
+    pub(crate) insert_size: Option<InsertSizeRange>,
+}
+
+/// A catalogue file looks like
+///
+/// ```toml
+/// [datasets.tumor]
+/// reference = "ref.fa"
+/// alignments = "tumor.bam"
+/// variants = "calls.vcf.gz"
+/// annotation = "genes.gtf"
+/// description = "Tumor sample"
+/// tracks = ["reference", "variants", "alignments"]
+/// insert_size = { min = 200, max = 600 }
+/// ```
+#[derive(Deserialize)]
+struct Catalogue {
+    datasets: BTreeMap<String, DatasetConfig>,
+}
+
+/// Summary of a dataset for the dataset list of the API.
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct DatasetInfo {
+    pub(crate) name: String,
+    pub(crate) description: Option<String>,
+}
+
+/// Open files, caches and search index of one dataset.
+pub struct Dataset {
+    pub(crate) config: DatasetConfig,
+    pub(crate) pool: ReaderPool,
+    pub(crate) caches: Caches,
+    pub(crate) layouts: Layouts,
+    pub(crate) name_index: NameIndex,
+    pub(crate) tracks: Tracks,
+}
+
+impl Dataset {
+    pub fn open(config: DatasetConfig, cache_size: usize) -> Result<Dataset, Box<dyn Error>> {
+        let tracks = match &config.tracks {
+            Some(tracks) => Tracks::from_str(&tracks.join(","))?,
+            None => Tracks::default(),
+        };
+
+        let insert_sizes = match config.insert_size {
+            Some(insert_sizes) => Some(insert_sizes),
+            None => InsertSizeRange::estimate(&config.alignments, INSERT_SIZE_SAMPLE)?,
+        };
+
+        Ok(Dataset {
+            pool: ReaderPool::new(&config.reference, &config.alignments, &config.variants),
+            caches: Caches::new(cache_size, insert_sizes),
+            layouts: Layouts::new(cache_size),
+            name_index: NameIndex::build(
+                config.annotation.as_ref().map(|p| p.as_path()),
+                &config.variants,
+            )?,
+            tracks: tracks,
+            config: config,
+        })
+    }
+}
+
+/// All datasets of the server. When they come from a catalogue file, the file is checked for
+/// changes on every access and reloaded in the background, while requests are still served from
+/// the previous datasets. Datasets whose entry did not change are kept open, and changed datasets
+/// that can't be opened keep their previous version.
+pub struct Datasets {
+    catalogue_path: Option<PathBuf>,
+    cache_size: usize,
+    state: Arc<RwLock<CatalogueState>>,
+    // Set while a thread reloads the catalogue, so that only one reload runs at a time
+    reloading: Arc<AtomicBool>,
+}
+
+struct CatalogueState {
+    // Hash of the catalogue file's contents when it was last read
+    version: Option<u64>,
+    datasets: BTreeMap<String, Arc<Dataset>>,
+}
+
+impl Datasets {
+    /// Serves a single dataset under the name `default`.
+    pub fn single(config: DatasetConfig, cache_size: usize) -> Result<Datasets, Box<dyn Error>> {
+        let mut datasets = BTreeMap::new();
+        datasets.insert(
+            DEFAULT_DATASET.to_owned(),
+            Arc::new(Dataset::open(config, cache_size)?),
+        );
+
+        Ok(Datasets {
+            catalogue_path: None,
+            cache_size: cache_size,
+            state: Arc::new(RwLock::new(CatalogueState {
+                version: None,
+                datasets: datasets,
+            })),
+            reloading: Arc::new(AtomicBool::new(false)),
+        })
+    }
+
+    pub fn from_catalogue(path: &Path, cache_size: usize) -> Result<Datasets, Box<dyn Error>> {
+        let contents = fs::read_to_string(path)?;
+        let mut datasets = BTreeMap::new();
+
+        for (name, config) in read_catalogue(path, &contents)? {
+            let dataset = Dataset::open(config, cache_size)
+                .map_err(|e| format!("could not open dataset '{}': {}", name, e))?;
+            datasets.insert(name, Arc::new(dataset));
+        }
+
+        Ok(Datasets {
+            catalogue_path: Some(path.to_owned()),
+            cache_size: cache_size,
+            state: Arc::new(RwLock::new(CatalogueState {
+                version: Some(content_version(&contents)),
+                datasets: datasets,
+            })),
+            reloading: Arc::new(AtomicBool::new(false)),
+        })
+    }
+
+    pub fn get(&self, name: &str) -> Option<Arc<Dataset>> {
+        self.reload_if_changed();
+        self.state.read().unwrap().datasets.get(name).cloned()
+    }
+
+    pub fn list(&self) -> Vec<DatasetInfo> {
+        self.reload_if_changed();
+        self.state
+            .read()
+            .unwrap()
+            .datasets
+            .iter()
+            .map(|(name, dataset)| DatasetInfo {
+                name: name.clone(),
+                description: dataset.config.description.clone(),
+            })
+            .collect()
+    }
+
+    /// Starts a reload when the catalogue file changed and no reload is running yet. Opening the
+    /// changed datasets can take a while when their insert sizes are estimated, so it is left to
+    /// a thread and the request returns at once.
+    fn reload_if_changed(&self) {
+        let path = match &self.catalogue_path {
+            Some(path) => path,
+            None => return,
+        };
+        let version = match fs::read_to_string(path) {
+            Ok(contents) => content_version(&contents),
+            Err(e) => {
+                warn!("Could not check catalogue {}: {}", path.display(), e);
+                return;
+            }
+        };
+        if self.state.read().unwrap().version == Some(version)
+            || self
+                .reloading
+                .compare_exchange(false, true, Ordering::SeqCst, Ordering::SeqCst)
+                .is_err()
+        {
+            return;
+        }
+
+        let path = path.clone();
+        let cache_size = self.cache_size;
+        let state = self.state.clone();
+        let reloading = self.reloading.clone();
+        thread::spawn(move || {
+            reload(&path, cache_size, &state);
+            reloading.store(false, Ordering::SeqCst);
+        });
+    }
+
+    /// Waits until a running reload is done.
+    #[cfg(test)]
+    pub fn wait_for_reload(&self) {
+        while self.reloading.load(Ordering::SeqCst) {
+            thread::sleep(std::time::Duration::from_millis(10));
+        }
+    }
+}
+
+/// Reads the catalogue again and swaps in its datasets. The state is only locked to take the
+/// current datasets and to replace them, not while datasets are opened.
+fn reload(path: &Path, cache_size: usize, state: &RwLock<CatalogueState>) {
+    let current = state.read().unwrap().datasets.clone();
+    let contents = match fs::read_to_string(path) {
+        Ok(contents) => contents,
+        Err(e) => {
+            warn!("Could not read catalogue {}: {}", path.display(), e);
+            return;
+        }
+    };
+
+    let mut datasets = BTreeMap::new();
+    match read_catalogue(path, &contents) {
+        Ok(catalogue) => {
+            for (name, config) in catalogue {
+                match current.get(&name) {
+                    Some(dataset) if dataset.config == config => {
+                        datasets.insert(name, dataset.clone());
+                    }
+                    previous => match Dataset::open(config, cache_size) {
+                        Ok(dataset) => {
+                            datasets.insert(name, Arc::new(dataset));
+                        }
+                        Err(e) => {
+                            warn!("Could not open dataset '{}': {}", name, e);
+                            if let Some(previous) = previous {
+                                datasets.insert(name, previous.clone());
+                            }
+                        }
+                    },
+                }
+            }
+        }
+        Err(e) => {
+            warn!(
+                "Keeping the previous datasets, could not read catalogue: {}",
+                e
+            );
+            datasets = current;
+        }
+    }
+
+    let mut state = state.write().unwrap();
+    state.version = Some(content_version(&contents));
+    state.datasets = datasets;
+}
+
+// A hash of the whole file instead of its modification time, which misses rewrites within the
+// resolution of the file system's timestamps
+fn content_version(contents: &str) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    contents.hash(&mut hasher);
+    hasher.finish()
+}
+
+fn read_catalogue(
+    path: &Path,
+    contents: &str,
+) -> Result<BTreeMap<String, DatasetConfig>, Box<dyn Error>> {
+    let catalogue: Catalogue = toml::from_str(contents)?;
+    let base = path.parent().unwrap_or_else(|| Path::new(""));
+
+    Ok(catalogue
+        .datasets
+        .into_iter()
+        .map(|(name, mut config)| {
+            config.reference = base.join(&config.reference);
+            config.alignments = base.join(&config.alignments);
+            config.variants = base.join(&config.variants);
+            config.annotation = config.annotation.map(|a| base.join(a));
+            (name, config)
+        })
+        .collect())
+}
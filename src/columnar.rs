@@ -0,0 +1,141 @@
+use alignment_reader::{AlignmentMatch, AlignmentNucleobase, Marker, Strand};
+use allele_support::Allele;
+use pairs::PairClass;
+use std::collections::HashMap;
+
+/// Attributes that are the same for all nucleobases and matches of a read, stored once per read.
+#[derive(Serialize, Clone, Debug, Default, PartialEq)]
+pub struct ReadTable {
+    pub(crate) name: Vec<String>,
+    pub(crate) flags: Vec<u16>,
+    pub(crate) read_start: Vec<u32>,
+    pub(crate) read_end: Vec<u32>,
+    pub(crate) row: Vec<u32>,
+    pub(crate) pair_class: Vec<PairClass>,
+    pub(crate) strand: Vec<Strand>,
+    // Only set for reads that overlap a variant
+    pub(crate) allele: Vec<Option<Allele>>,
+}
+
+/// One entry per nucleobase or match, pointing to its read by index into the read table.
+#[derive(Serialize, Clone, Debug, Default, PartialEq)]
+pub struct EventTable {
+    pub(crate) read: Vec<u32>,
+    pub(crate) marker_type: Vec<Marker>,
+    pub(crate) start_position: Vec<f64>,
+    pub(crate) end_position: Vec<f64>,
+    pub(crate) bases: Vec<String>,
+    // Only set for modified bases
+    pub(crate) probability: Vec<Option<f64>>,
+}
+
+/// Column oriented alignments, which are much smaller than a list of nucleobase objects
+/// because the read attributes are not repeated for every base.
+#[derive(Serialize, Clone, Debug, Default, PartialEq)]
+pub struct ColumnarAlignments {
+    pub(crate) reads: ReadTable,
+    pub(crate) events: EventTable,
+}
+
+impl ColumnarAlignments {
+    pub fn new(bases: &[AlignmentNucleobase], matches: &[AlignmentMatch]) -> ColumnarAlignments {
+        let mut columns = ColumnarAlignments::default();
+        let mut read_indices: HashMap<(&str, u16), u32> = HashMap::new();
+
+        for m in matches {
+            let read = columns.read_index(
+                &mut read_indices,
+                &m.name,
+                &m.flags,
+                m.read_start,
+                m.read_end,
+                m.row,
+                m.pair_class,
+                m.strand,
+                m.allele,
+            );
+            columns.push_event(
+                read,
+                &m.marker_type,
+                m.start_position,
+                m.end_position,
+                "",
+                None,
+            );
+        }
+
+        for b in bases {
+            let read = columns.read_index(
+                &mut read_indices,
+                &b.name,
+                &b.flags,
+                b.read_start,
+                b.read_end,
+                b.row,
+                b.pair_class,
+                b.strand,
+                b.allele,
+            );
+            columns.push_event(
+                read,
+                &b.marker_type,
+                b.start_position,
+                b.end_position,
+                &b.bases,
+                b.probability,
+            );
+        }
+
+        columns
+    }
+
+    fn read_index<'a>(
+        &mut self,
+        read_indices: &mut HashMap<(&'a str, u16), u32>,
+        name: &'a str,
+        flags: &[u16],
+        read_start: u32,
+        read_end: u32,
+        row: u32,
+        pair_class: PairClass,
+        strand: Strand,
+        allele: Option<Allele>,
+    ) -> u32 {
+        // The decoded flags are stored as the original bit mask again. Both mates of a pair
+        // have the same name, but different flags.
+        let flags = flags.iter().fold(0, |mask, flag| mask | flag);
+        if let Some(index) = read_indices.get(&(name, flags)) {
+            return *index;
+        }
+
+        let index = self.reads.name.len() as u32;
+        self.reads.name.push(name.to_owned());
+        self.reads.flags.push(flags);
+        self.reads.read_start.push(read_start);
+        self.reads.read_end.push(read_end);
+        self.reads.row.push(row);
+        self.reads.pair_class.push(pair_class);
+        self.reads.strand.push(strand);
+        self.reads.allele.push(allele);
+        read_indices.insert((name, flags), index);
+
+        index
+    }
+
+    fn push_event(
+        &mut self,
+        read: u32,
+        marker_type: &Marker,
+        start_position: f64,
+        end_position: f64,
+        bases: &str,
+        probability: Option<f64>,
+    ) {
+        self.events.read.push(read);
+        self.events.marker_type.push(marker_type.clone());
+        self.events.start_position.push(start_position);
+        self.events.end_position.push(end_position);
+        self.events.bases.push(bases.to_owned());
+        self.events.probability.push(probability);
+    }
+}
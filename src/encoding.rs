@@ -0,0 +1,60 @@
+use rocket::http::{ContentType, Status};
+use rocket::request::{self, FromRequest, Request};
+use rocket::response::{self, Responder, Response};
+use rocket::Outcome;
+use rocket_contrib::json::Json;
+use serde::Serialize;
+use std::io::Cursor;
+
+/// Request guard telling whether the client prefers MessagePack over JSON
+/// (`Accept: application/x-msgpack` or `application/msgpack`).
+pub struct Encoding {
+    pub(crate) msgpack: bool,
+}
+
+impl<'a, 'r> FromRequest<'a, 'r> for Encoding {
+    type Error = ();
+
+    fn from_request(request: &'a Request<'r>) -> request::Outcome<Encoding, ()> {
+        let msgpack = match request.accept() {
+            Some(accept) => {
+                let preferred = accept.preferred().media_type();
+                preferred.top() == "application"
+                    && (preferred.sub() == "x-msgpack" || preferred.sub() == "msgpack")
+            }
+            None => false,
+        };
+
+        Outcome::Success(Encoding { msgpack: msgpack })
+    }
+}
+
+/// A response that is either sent as JSON or as MessagePack.
+pub enum Encoded<J, M> {
+    Json(Json<J>),
+    MsgPack(M),
+}
+
+impl<'r, J: Serialize, M: Serialize> Responder<'r> for Encoded<J, M> {
+    fn respond_to(self, request: &Request) -> response::Result<'r> {
+        match self {
+            Encoded::Json(json) => json.respond_to(request),
+            Encoded::MsgPack(value) => {
+                let bytes = to_msgpack(&value).map_err(|e| {
+                    error!("Could not encode MessagePack response: {}", e);
+                    Status::InternalServerError
+                })?;
+                Response::build()
+                    .header(ContentType::MsgPack)
+                    .sized_body(Cursor::new(bytes))
+                    .ok()
+            }
+        }
+    }
+}
+
+/// Encodes structs as maps with field names, like JSON objects. Rocket's `MsgPack` encodes them
+/// as arrays, where the fields after a skipped `None` would be taken for the wrong ones.
+pub fn to_msgpack<T: Serialize>(value: &T) -> Result<Vec<u8>, rmp_serde::encode::Error> {
+    rmp_serde::to_vec_named(value)
+}
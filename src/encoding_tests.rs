@@ -0,0 +1,73 @@
+use super::*;
+use rocket::http::{Accept, ContentType, Status};
+use rocket::local::Client;
+use serde_json::Value;
+
+fn client() -> Client {
+    let dataset = Datasets::single(
+        DatasetConfig {
+            reference: PathBuf::from("tests/resources/ref.fa"),
+            alignments: PathBuf::from("tests/resources/test.bam"),
+            variants: PathBuf::from("tests/resources/report-test.vcf.gz"),
+            annotation: None,
+            description: None,
+            tracks: None,
+            insert_size: None,
+        },
+        4,
+    )
+    .unwrap();
+    let server = rocket::ignite()
+        .manage(dataset)
+        .manage(None::<Access>)
+        .manage(LevelOfDetail::default())
+        .mount("/api/v1", routes![region, alignment]);
+    Client::new(server).unwrap()
+}
+
+fn get_msgpack(client: &Client, uri: &str) -> Value {
+    let mut response = client
+        .get(uri.to_owned())
+        .header(Accept::new(vec![ContentType::MsgPack.into()]))
+        .dispatch();
+    assert_eq!(response.status(), Status::Ok);
+    assert_eq!(response.content_type(), Some(ContentType::MsgPack));
+    rmp_serde::from_slice(&response.body_bytes().unwrap()).unwrap()
+}
+
+#[test]
+fn msgpack_region_test() {
+    let client = client();
+
+    // Skipped tracks must not shift the fields that follow them
+    let data = get_msgpack(
+        &client,
+        "/api/v1/region/chr1/0/60?tracks=reference,coverage",
+    );
+    assert_eq!(data["region"]["chromosome"], "chr1");
+    assert_eq!(data["reference"].as_array().unwrap().len(), 60);
+    assert_eq!(data["coverage"].as_array().unwrap().len(), 60);
+    assert!(data.get("variants").is_none());
+    assert!(data.get("alignments").is_none());
+
+    let data = get_msgpack(&client, "/api/v1/region/chr1/0/60");
+    assert_eq!(data["variants"].as_array().unwrap().len(), 2);
+    assert!(data.get("matches").is_none());
+    let reads = &data["alignments"]["reads"];
+    assert!(!reads["name"].as_array().unwrap().is_empty());
+    assert_eq!(
+        reads["name"].as_array().unwrap().len(),
+        reads["row"].as_array().unwrap().len()
+    );
+}
+
+#[test]
+fn msgpack_alignment_test() {
+    let client = client();
+
+    let data = get_msgpack(&client, "/api/v1/alignment/chr1/0/60");
+    let (alignments, groups) = (&data[0], &data[1]);
+    assert!(!alignments["reads"]["name"].as_array().unwrap().is_empty());
+    assert!(alignments["events"]["marker_type"].is_array());
+    assert!(groups.is_array());
+}
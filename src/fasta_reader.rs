@@ -1,30 +1,76 @@
 use bio::io::fasta;
+use region::UnknownChromosome;
+use std::error::Error;
+use std::fs::File;
+use std::ops::Not;
 use std::path::Path;
 
-pub fn read_fasta(path: &Path, chrom: String, start: u64, stop: u64) -> Vec<Nucleobase> {
-    let mut reader = fasta::IndexedReader::from_file(&path).unwrap();
-    let index = fasta::Index::with_fasta_file(&path).unwrap();
-    let _sequences = index.sequences();
+pub fn read_fasta(
+    path: &Path,
+    chrom: String,
+    start: u64,
+    stop: u64,
+) -> Result<Vec<Nucleobase>, Box<dyn Error>> {
+    let mut reader = fasta::IndexedReader::from_file(&path)?;
+
+    fetch_fasta(&mut reader, chrom, start, stop)
+}
+
+pub fn fetch_fasta(
+    reader: &mut fasta::IndexedReader<File>,
+    chrom: String,
+    start: u64,
+    stop: u64,
+) -> Result<Vec<Nucleobase>, Box<dyn Error>> {
+    if !reader.index.sequences().iter().any(|s| s.name == chrom) {
+        return Err(UnknownChromosome(chrom).into());
+    }
 
     let mut seq: Vec<u8> = Vec::new();
 
-    reader.fetch(&chrom, start, stop).unwrap();
-    reader.read(&mut seq).unwrap();
+    reader.fetch(&chrom, start, stop)?;
+    reader.read(&mut seq)?;
 
     let mut fasta = Vec::new();
     let mut ind = start;
     for a in seq {
+        // Soft-masked bases are shown like other bases, but shaded as repeats
         let b = Nucleobase {
             start_position: ind as f64 - 0.5,
             end_position: ind as f64 + 0.5,
-            marker_type: a as char,
+            marker_type: a.to_ascii_uppercase() as char,
             row: 0,
+            soft_masked: a.is_ascii_lowercase(),
         };
         fasta.push(b);
         ind += 1;
     }
 
-    fasta
+    Ok(fasta)
+}
+
+/// Like `fetch_fasta`, but with `padding` more bases on both sides, as far as the chromosome
+/// reaches.
+pub fn fetch_padded_fasta(
+    reader: &mut fasta::IndexedReader<File>,
+    chrom: String,
+    start: u64,
+    stop: u64,
+    padding: u64,
+) -> Result<Vec<Nucleobase>, Box<dyn Error>> {
+    let length = reader
+        .index
+        .sequences()
+        .iter()
+        .find(|s| s.name == chrom)
+        .map_or(stop, |s| s.len);
+
+    fetch_fasta(
+        reader,
+        chrom,
+        start.saturating_sub(padding),
+        (stop + padding).min(length),
+    )
 }
 
 pub fn get_fasta_length(path: &Path) -> u64 {
@@ -42,6 +88,9 @@ pub struct Nucleobase {
     pub(crate) end_position: f64,
     pub(crate) marker_type: char,
     pub(crate) row: u8,
+    // Lower case in the fasta file, usually a repeat
+    #[serde(skip_serializing_if = "Not::not")]
+    pub(crate) soft_masked: bool,
 }
 
 impl Nucleobase {
@@ -0,0 +1,164 @@
+use aux_tags::{aux_integer, aux_string};
+use rust_htslib::bam;
+use std::collections::HashMap;
+use std::str::FromStr;
+
+/// Attributes of a read that reads can be grouped by, the same for both mates of a pair.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ReadInfo {
+    // Strand of the first read of the pair, like IGV's "first-of-pair strand"
+    pub(crate) reverse: bool,
+    pub(crate) haplotype: Option<i64>,
+    pub(crate) phase_set: Option<i64>,
+    pub(crate) read_group: Option<String>,
+    pub(crate) sample: Option<String>,
+    pub(crate) mate_chromosome: Option<String>,
+}
+
+/// Read infos of a region by read name.
+pub type ReadInfos = HashMap<String, ReadInfo>;
+
+impl ReadInfo {
+    pub fn new(
+        record: &bam::Record,
+        header: &bam::HeaderView,
+        samples: &HashMap<String, String>,
+    ) -> ReadInfo {
+        let reverse = if record.is_paired() && record.is_last_in_template() {
+            record.is_mate_reverse()
+        } else {
+            record.is_reverse()
+        };
+        let read_group = aux_string(record, b"RG");
+        let mate_chromosome =
+            if record.is_paired() && !record.is_mate_unmapped() && record.mtid() >= 0 {
+                Some(String::from_utf8_lossy(header.tid2name(record.mtid() as u32)).into_owned())
+            } else {
+                None
+            };
+
+        ReadInfo {
+            reverse: reverse,
+            haplotype: aux_integer(record, b"HP"),
+            phase_set: aux_integer(record, b"PS"),
+            sample: read_group.as_ref().and_then(|rg| samples.get(rg).cloned()),
+            read_group: read_group,
+            mate_chromosome: mate_chromosome,
+        }
+    }
+}
+
+/// Samples of the read groups from the `@RG` lines of a bam header.
+pub fn samples_by_read_group(header: &bam::HeaderView) -> HashMap<String, String> {
+    let text = String::from_utf8_lossy(header.as_bytes()).into_owned();
+    let mut samples = HashMap::new();
+
+    for line in text.lines().filter(|line| line.starts_with("@RG")) {
+        let field = |name: &str| {
+            line.split('\t')
+                .find(|f| f.starts_with(name))
+                .map(|f| f[name.len()..].to_owned())
+        };
+        if let (Some(id), Some(sample)) = (field("ID:"), field("SM:")) {
+            samples.insert(id, sample);
+        }
+    }
+
+    samples
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum GroupBy {
+    Strand,
+    Haplotype,
+    ReadGroup,
+    Sample,
+    MateChromosome,
+}
+
+impl FromStr for GroupBy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<GroupBy, String> {
+        match s {
+            "strand" => Ok(GroupBy::Strand),
+            "haplotype" => Ok(GroupBy::Haplotype),
+            "read-group" => Ok(GroupBy::ReadGroup),
+            "sample" => Ok(GroupBy::Sample),
+            "mate-chromosome" => Ok(GroupBy::MateChromosome),
+            _ => Err(format!("unknown grouping '{}'", s)),
+        }
+    }
+}
+
+/// Sort key of a group. Groups are ordered by the numbers of their tags, then by name, and reads
+/// without the tag come last.
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct GroupKey {
+    missing: bool,
+    numbers: Vec<i64>,
+    pub(crate) label: String,
+}
+
+impl GroupBy {
+    pub fn key(&self, info: &ReadInfo) -> GroupKey {
+        let named = |name: &Option<String>, none: &str| match name {
+            Some(name) => GroupKey {
+                missing: false,
+                numbers: vec![],
+                label: name.clone(),
+            },
+            None => GroupKey {
+                missing: true,
+                numbers: vec![],
+                label: none.to_owned(),
+            },
+        };
+
+        match self {
+            GroupBy::Strand => GroupKey {
+                missing: false,
+                numbers: vec![info.reverse as i64],
+                label: String::from(if info.reverse { "reverse" } else { "forward" }),
+            },
+            GroupBy::Haplotype => match (info.phase_set, info.haplotype) {
+                (_, None) => named(&None, "no haplotype"),
+                (Some(ps), Some(hp)) => GroupKey {
+                    missing: false,
+                    numbers: vec![ps, hp],
+                    label: format!("PS {} HP {}", ps, hp),
+                },
+                (None, Some(hp)) => GroupKey {
+                    missing: false,
+                    numbers: vec![-1, hp],
+                    label: format!("HP {}", hp),
+                },
+            },
+            GroupBy::ReadGroup => named(&info.read_group, "no read group"),
+            GroupBy::Sample => named(&info.sample, "no sample"),
+            GroupBy::MateChromosome => named(&info.mate_chromosome, "no mate"),
+        }
+    }
+}
+
+/// How the reads of a region are ordered into rows. Without grouping and sorting the rows of the
+/// server stay the same while scrolling.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct ReadOrder {
+    pub(crate) group_by: Option<GroupBy>,
+    // Reads are sorted by their base at this position
+    pub(crate) sort_by_base: Option<u64>,
+}
+
+impl ReadOrder {
+    pub fn is_default(&self) -> bool {
+        self.group_by.is_none() && self.sort_by_base.is_none()
+    }
+}
+
+/// The first row of a group, which is left empty and labeled with the group name in the plots.
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct GroupSeparator {
+    pub(crate) group: String,
+    pub(crate) row: u32,
+}
@@ -0,0 +1,126 @@
+use super::*;
+use alignment_reader::{fetch_alignments, make_nucleobases, read_infos};
+use fasta_reader::{fetch_fasta, read_fasta};
+use grouping::GroupSeparator;
+use layout::assign_grouped;
+use rust_htslib::bam;
+use static_reader::fetch_static_reads;
+use std::collections::BTreeMap;
+
+// Rows of the reads and the group separators of haplotypes.bam
+fn grouped_rows(order: ReadOrder) -> (BTreeMap<String, u32>, Vec<GroupSeparator>) {
+    let mut bam =
+        bam::IndexedReader::from_path(Path::new("tests/resources/haplotypes.bam")).unwrap();
+    let alignments = fetch_alignments(&mut bam, String::from("chr1"), 0, 60).unwrap();
+    let infos = read_infos(&alignments);
+    let ref_bases = read_fasta(
+        Path::new("tests/resources/ref.fa"),
+        String::from("chr1"),
+        0,
+        60,
+    )
+    .unwrap();
+    let (mut bases, mut matches) = make_nucleobases(&ref_bases, alignments, 0, 60);
+    let groups = assign_grouped(&mut bases, &mut matches, &infos, &order);
+
+    let rows = matches
+        .iter()
+        .map(|m| (m.name.clone(), m.row))
+        .chain(bases.iter().map(|b| (b.name.clone(), b.row)))
+        .collect();
+    (rows, groups)
+}
+
+fn separator(group: &str, row: u32) -> GroupSeparator {
+    GroupSeparator {
+        group: String::from(group),
+        row: row,
+    }
+}
+
+#[test]
+fn group_by_haplotype_test() {
+    let (rows, groups) = grouped_rows(ReadOrder {
+        group_by: Some(GroupBy::Haplotype),
+        sort_by_base: None,
+    });
+
+    // The reads of a haplotype overlap, so each one gets its own row below the separator
+    assert_eq!(
+        groups,
+        vec![
+            separator("PS 100 HP 1", 1),
+            separator("PS 100 HP 2", 4),
+            separator("no haplotype", 7),
+        ]
+    );
+    assert_eq!(rows["h1_a"], 2);
+    assert_eq!(rows["h1_b"], 3);
+    assert_eq!(rows["h2_a"], 5);
+    assert_eq!(rows["h2_b"], 6);
+    assert_eq!(rows["untagged"], 8);
+}
+
+#[test]
+fn group_by_attributes_test() {
+    let group_names = |group_by| {
+        let (_, groups) = grouped_rows(ReadOrder {
+            group_by: Some(group_by),
+            sort_by_base: None,
+        });
+        groups.into_iter().map(|g| g.group).collect::<Vec<_>>()
+    };
+
+    assert_eq!(group_names(GroupBy::Strand), vec!["forward", "reverse"]);
+    assert_eq!(group_names(GroupBy::ReadGroup), vec!["a", "b"]);
+    assert_eq!(group_names(GroupBy::Sample), vec!["s1", "s2"]);
+    assert_eq!(group_names(GroupBy::MateChromosome), vec!["no mate"]);
+
+    assert_eq!(GroupBy::from_str("read-group"), Ok(GroupBy::ReadGroup));
+    assert!(GroupBy::from_str("flag").is_err());
+}
+
+#[test]
+fn sort_by_base_test() {
+    let (rows, groups) = grouped_rows(ReadOrder {
+        group_by: None,
+        sort_by_base: Some(19),
+    });
+
+    // Reads with a T instead of the reference G at position 19 come first
+    assert!(groups.is_empty());
+    assert_eq!(rows["h2_a"], 1);
+    assert_eq!(rows["h2_b"], 2);
+    assert!(rows["h1_a"] > 2 && rows["h1_b"] > 2 && rows["untagged"] > 2);
+}
+
+#[test]
+fn grouped_report_data_test() {
+    let order = ReadOrder {
+        group_by: Some(GroupBy::Haplotype),
+        sort_by_base: None,
+    };
+    let mut readers = Readers::open(
+        Path::new("tests/resources/ref.fa"),
+        Path::new("tests/resources/haplotypes.bam"),
+        Path::new("tests/resources/report-test.vcf.gz"),
+    )
+    .unwrap();
+    let reference = fetch_fasta(&mut readers.fasta, String::from("chr1"), 0, 60).unwrap();
+    let (_bases, matches, groups) = fetch_static_reads(
+        &mut readers,
+        &reference,
+        String::from("chr1"),
+        0,
+        60,
+        &order,
+        None,
+        0,
+    )
+    .unwrap();
+
+    assert_eq!(groups.len(), 3);
+    assert_eq!(groups[1], separator("PS 100 HP 2", 4));
+    let untagged = matches.iter().find(|m| m.name == "untagged").unwrap();
+    assert_eq!(untagged.row, 8);
+}
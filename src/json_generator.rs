@@ -1,79 +1,372 @@
-use fasta_reader::read_fasta;
+use grouping::GroupSeparator;
+use modifications::MethylationLevel;
 use rustc_serialize::json::Json;
 use serde_json::Value;
-use static_reader::{get_static_reads, get_static_variants};
+use static_reader::StaticData;
+use std::collections::HashMap;
+use std::error::Error;
 use std::fs;
 use std::path::Path;
+use theme::{PlotOptions, Theme, MARKER_TYPES};
 
-pub fn create_data(
-    fasta_path: &Path,
-    vcf_path: &Path,
-    bam_path: &Path,
-    chrom: String,
-    from: u64,
-    to: u64,
-) -> Json {
+const DEFAULT_SPECS: &str = include_str!("../static/vegaSpecs.json");
+
+// Variants use the rows -1 to -9
+const METHYLATION_ROW: i32 = -10;
+
+/// Returns the given custom vega specs or the default specs that are compiled into the binary.
+pub fn read_specs(path: Option<&Path>) -> Result<String, Box<dyn Error>> {
+    match path {
+        Some(path) => {
+            let specs = fs::read_to_string(path)?;
+            serde_json::from_str::<Value>(&specs)?;
+            Ok(specs)
+        }
+        None => Ok(DEFAULT_SPECS.to_owned()),
+    }
+}
+
+pub fn create_data(static_data: &StaticData) -> Json {
     let mut data = Vec::new();
 
-    for f in read_fasta(fasta_path.clone(), chrom.clone(), from, to) {
+    for f in static_data.reference.iter() {
         let nucleobase = json!(f);
         data.push(nucleobase);
     }
-    let (bases, matches) = get_static_reads(bam_path, fasta_path, chrom.clone(), from, to);
 
-    for b in bases {
+    for b in static_data.bases.iter() {
         let base = json!(b);
         data.push(base);
     }
 
-    for m in matches {
+    for m in static_data.matches.iter() {
         let mat = json!(m);
         data.push(mat);
     }
 
-    for v in get_static_variants(vcf_path, chrom.clone(), from, to) {
+    for v in static_data.variants.iter() {
         let variant = json!(v);
         data.push(variant);
     }
 
+    for g in static_data.groups.iter() {
+        data.push(group_separator(g));
+    }
+
+    for m in static_data.methylation.iter() {
+        data.push(methylation_level(m));
+    }
+
     let values = Json::from_str(&json!(data).to_string()).unwrap();
 
     values
 }
 
-pub fn manipulate_json(data: Json, from: u64, to: u64) -> Value {
-    let vega_path = Path::new("./static/vegaSpecs.json");
-    let json_string = fs::read_to_string(vega_path).unwrap();
+pub fn manipulate_json(
+    data: Json,
+    from: u64,
+    to: u64,
+    specs: &str,
+    options: &PlotOptions,
+) -> Value {
+    let mut vega_specs: Value = serde_json::from_str(specs).unwrap();
+    let values = json!({"values": prepare_values(data), "name": "fasta"});
 
-    let mut vega_specs: Value = serde_json::from_str(&json_string).unwrap();
-    let values: Value = serde_json::from_str(&data.to_string()).unwrap();
-    let mut values = json!({"values": values, "name": "fasta"});
+    vega_specs["width"] = json!(700);
+    let domain = json!([from, to]);
 
-    let v = values["values"].as_array().unwrap().clone();
+    vega_specs["scales"][0]["domain"] = domain;
+    vega_specs["data"][1] = values;
 
-    for i in 0..v.len() {
-        let k = v[i]["marker_type"].clone().as_str().unwrap().to_owned();
+    if let Some(height) = options.height {
+        vega_specs["height"] = json!(height);
+    }
 
-        if k == "A" || k == "T" || k == "G" || k == "C" || k == "U" {
-            values["values"][i]["base"] = values["values"][i]["marker_type"].clone();
+    if let Some(signals) = vega_specs["signals"].as_array_mut() {
+        for signal in signals.iter_mut() {
+            if signal["name"] == "color_by_strand" {
+                signal["value"] = json!(options.color_by_strand);
+            }
+        }
+    }
+
+    if let Some(scales) = vega_specs["scales"].as_array_mut() {
+        for scale in scales.iter_mut() {
+            if scale["name"] == "y" {
+                if let Some(row_spacing) = options.row_spacing {
+                    scale["paddingInner"] = json!(row_spacing);
+                }
+            } else if scale["name"] == "color" && options.theme != Theme::Default {
+                // Only the colors of known marker types are replaced, so custom specs keep their own
+                let domain = scale["domain"].clone();
+                if let Some(domain) = domain.as_array() {
+                    for (i, marker_type) in domain.iter().enumerate() {
+                        let color = marker_type.as_str().and_then(|m| options.theme.color(m));
+                        if let Some(color) = color {
+                            scale["range"][i] = json!(color);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    vega_specs
+}
+
+/// Like `manipulate_json`, but the name and flags of a read are only stored once in a separate
+/// `reads` data set, which vega joins back to the bases and matches of the read.
+pub fn compact_json(
+    static_data: &StaticData,
+    from: u64,
+    to: u64,
+    specs: &str,
+    options: &PlotOptions,
+) -> Value {
+    let mut data = Vec::new();
+    let mut reads = Vec::new();
+    let mut read_indices = HashMap::new();
+
+    for f in static_data.reference.iter() {
+        data.push(json!(f));
+    }
+
+    for v in static_data.variants.iter() {
+        data.push(json!(v));
+    }
+
+    for g in static_data.groups.iter() {
+        data.push(group_separator(g));
+    }
+
+    for m in static_data.methylation.iter() {
+        data.push(methylation_level(m));
+    }
+
+    let events = static_data
+        .matches
+        .iter()
+        .map(|m| {
+            (
+                &m.name,
+                &m.flags,
+                m.strand,
+                m.pair_class,
+                m.allele,
+                &m.marker_type,
+                m.start_position,
+                m.end_position,
+                m.row,
+                "",
+                None,
+            )
+        })
+        .chain(static_data.bases.iter().map(|b| {
+            (
+                &b.name,
+                &b.flags,
+                b.strand,
+                b.pair_class,
+                b.allele,
+                &b.marker_type,
+                b.start_position,
+                b.end_position,
+                b.row,
+                &b.bases[..],
+                b.probability,
+            )
+        }));
+
+    for (
+        name,
+        flags,
+        strand,
+        pair_class,
+        allele,
+        marker_type,
+        start,
+        end,
+        row,
+        bases,
+        probability,
+    ) in events
+    {
+        let read = *read_indices.entry((name, flags)).or_insert_with(|| {
+            let mut read = json!({
+                "read": reads.len(),
+                "name": name,
+                "flags": flags,
+                "strand": strand,
+                "pair_class": pair_class
+            });
+            if let Some(allele) = allele {
+                read["allele"] = json!(allele);
+            }
+            reads.push(read);
+            reads.len() - 1
+        });
+
+        let mut event = json!({
+            "marker_type": marker_type,
+            "start_position": start,
+            "end_position": end,
+            "row": row,
+            "read": read
+        });
+        if !bases.is_empty() {
+            event["bases"] = json!(bases);
+        }
+        if let Some(probability) = probability {
+            event["probability"] = json!(probability);
+        }
+        data.push(event);
+    }
+
+    let values = Json::from_str(&json!(data).to_string()).unwrap();
+    let mut vega_specs = manipulate_json(values, from, to, specs, options);
+
+    vega_specs["data"][1]["transform"] = json!([{
+        "type": "lookup",
+        "from": "reads",
+        "key": "read",
+        "fields": ["read"],
+        "values": ["name", "flags", "strand", "pair_class", "allele"]
+    }]);
+    vega_specs["data"]
+        .as_array_mut()
+        .unwrap()
+        .insert(1, json!({"name": "reads", "values": reads}));
+
+    vega_specs
+}
+
+/// Creates a layered vega-lite spec, which can be combined with other vega-lite or altair plots.
+pub fn vega_lite_spec(data: Json, from: u64, to: u64, options: &PlotOptions) -> Value {
+    let colors: Vec<&str> = MARKER_TYPES
+        .iter()
+        .map(|m| options.theme.color(m).unwrap())
+        .collect();
+    // Reads of unusual pairs are coloured by their pair class, like in IGV
+    let pair_colors = "(datum.marker_type === 'Match' || datum.marker_type === 'Pairing') && indexof(['InterChromosomal', 'LargeInsert', 'SmallInsert', 'ReverseForward', 'ForwardForward', 'ReverseReverse'], datum.pair_class) >= 0 ? datum.pair_class : datum.type";
+    // Matches are grey, or coloured by the strand of their read
+    let strand_colors = if options.color_by_strand {
+        "datum.marker_type === 'Match' ? datum.strand + 'Strand' : datum.type"
+    } else {
+        "datum.type"
+    };
+    // Ambiguity codes share the color of N
+    let ambiguous =
+        "indexof(['R', 'Y', 'S', 'W', 'K', 'M', 'B', 'D', 'H', 'V'], datum.type) >= 0 ? 'N' : datum.type";
+
+    json!({
+        "$schema": "https://vega.github.io/schema/vega-lite/v4.json",
+        "width": 700,
+        "height": options.height.unwrap_or(250),
+        "data": {"values": prepare_values(data)},
+        "transform": [
+            {"calculate": "datum.var_type || (datum.probability != null ? (datum.probability >= 0.5 ? 'Modified' : 'Unmodified') : datum.marker_type)", "as": "type"},
+            {"calculate": pair_colors, "as": "type"},
+            {"calculate": strand_colors, "as": "type"},
+            {"calculate": "datum.marker_type === 'Match' && datum.allele === 'Alt' ? 'AltSupport' : datum.type", "as": "type"},
+            {"calculate": ambiguous, "as": "type"}
+        ],
+        "mark": {"type": "rule", "clip": true},
+        "encoding": {
+            "x": {
+                "field": "start_position",
+                "type": "quantitative",
+                "scale": {"domain": [from, to], "zero": false},
+                "title": "Position"
+            },
+            "x2": {"field": "end_position"},
+            "opacity": {"condition": {"test": "datum.soft_masked", "value": 0.4}, "value": 0.8},
+            "y": {
+                "field": "row",
+                "type": "ordinal",
+                "axis": null,
+                "scale": {"paddingInner": options.row_spacing.unwrap_or(0.0)}
+            },
+            "color": {
+                "field": "type",
+                "type": "nominal",
+                "scale": {"domain": MARKER_TYPES.to_vec(), "range": colors},
+                "title": "Base"
+            },
+            "strokeWidth": {
+                "field": "marker_type",
+                "type": "nominal",
+                "scale": {
+                    "domain": ["A", "C", "G", "T", "N", "Variant", "Insertion", "Deletion", "Match", "Pairing", "SoftClip", "Split", "SplitEnd"],
+                    "range": [5, 5, 5, 5, 5, 5, 7.5, 5, 5, 1, 5, 1, 7.5]
+                },
+                "legend": null
+            },
+            "tooltip": [
+                {"field": "type", "type": "nominal"},
+                {"field": "name", "type": "nominal"},
+                {"field": "strand", "type": "nominal"},
+                {"field": "pair_class", "type": "nominal", "title": "pair"},
+                {"field": "allele", "type": "nominal"},
+                {"field": "inserts", "type": "nominal", "title": "inserted base(s)"},
+                {"field": "clipped", "type": "nominal", "title": "clipped base(s)"},
+                {"field": "partners", "type": "nominal", "title": "split to"},
+                {"field": "probability", "type": "quantitative", "format": ".2f"},
+                {"field": "reference", "type": "nominal"},
+                {"field": "alternatives", "type": "nominal"}
+            ]
+        }
+    })
+}
+
+/// Separators are marked as their own marker type, so the specs can draw them apart from reads.
+pub fn group_separator(group: &GroupSeparator) -> Value {
+    let mut separator = json!(group);
+    separator["marker_type"] = json!("Group");
+
+    separator
+}
+
+// Aggregated modified bases are drawn in their own row above the variants
+fn methylation_level(level: &MethylationLevel) -> Value {
+    let mut methylation = json!(level);
+    methylation["marker_type"] = json!("Methylation");
+    methylation["row"] = json!(METHYLATION_ROW);
+
+    methylation
+}
+
+// Nucleotide markers, including N and the IUPAC ambiguity codes, which the spec draws like N
+const NUCLEOTIDES: [&str; 16] = [
+    "A", "C", "G", "T", "U", "N", "R", "Y", "S", "W", "K", "M", "B", "D", "H", "V",
+];
+
+fn prepare_values(data: Json) -> Value {
+    let mut values: Value = serde_json::from_str(&data.to_string()).unwrap();
+
+    for v in values.as_array_mut().unwrap().iter_mut() {
+        let k = v["marker_type"].clone().as_str().unwrap().to_owned();
+
+        if NUCLEOTIDES.contains(&k.as_str()) {
+            v["base"] = v["marker_type"].clone();
         } else if k == "Deletion"
             || k == "Match"
             || k == "Pairing"
+            || k == "Split"
             || k == "Duplicate"
             || k == "Inversion"
         {
-            values["values"][i]["typ"] = values["values"][i]["marker_type"].clone();
+            v["typ"] = v["marker_type"].clone();
         } else if k == "Insertion" {
-            values["values"][i]["typ"] = values["values"][i]["marker_type"].clone();
-            values["values"][i]["inserts"] = values["values"][i]["bases"].clone();
+            v["typ"] = v["marker_type"].clone();
+            v["inserts"] = v["bases"].clone();
+        } else if k == "SoftClip" {
+            v["typ"] = v["marker_type"].clone();
+            v["clipped"] = v["bases"].clone();
+        } else if k == "SplitEnd" {
+            v["typ"] = v["marker_type"].clone();
+            v["partners"] = v["bases"].clone();
         }
     }
 
-    vega_specs["width"] = json!(700);
-    let domain = json!([from, to]);
-
-    vega_specs["scales"][0]["domain"] = domain;
-    vega_specs["data"][1] = values;
-
-    vega_specs
+    values
 }
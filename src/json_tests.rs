@@ -0,0 +1,121 @@
+use super::*;
+use rustc_serialize::json::Json;
+use std::path::Path;
+
+fn test_data() -> Json {
+    Json::from_str(
+        r#"[{"marker_type": "A", "start_position": 0.5, "end_position": 1.5, "row": 0}]"#,
+    )
+    .unwrap()
+}
+
+#[test]
+fn theme_test() {
+    let options = PlotOptions {
+        theme: Theme::Greyscale,
+        height: Some(400),
+        row_spacing: Some(0.2),
+        soft_clips: true,
+        min_indel_length: 0,
+        color_by_strand: false,
+        alt_only: false,
+        modification: None,
+    };
+    let specs = manipulate_json(test_data(), 0, 10, &read_specs(None).unwrap(), &options);
+
+    assert_eq!(specs["height"], 400);
+    assert_eq!(specs["scales"][1]["paddingInner"], 0.2);
+    assert_eq!(specs["scales"][4]["name"], "color");
+    assert_eq!(specs["scales"][4]["range"][0], "#404040");
+    assert_eq!(specs["data"][1]["values"][0]["base"], "A");
+}
+
+#[test]
+fn vega_lite_test() {
+    let specs = vega_lite_spec(test_data(), 0, 10, &PlotOptions::default());
+
+    assert_eq!(
+        specs["$schema"],
+        "https://vega.github.io/schema/vega-lite/v4.json"
+    );
+    assert_eq!(specs["encoding"]["x"]["scale"]["domain"], json!([0, 10]));
+    assert_eq!(specs["encoding"]["color"]["scale"]["range"][0], "#CADB69");
+    assert_eq!(specs["data"]["values"][0]["base"], "A");
+}
+
+#[test]
+fn strand_color_test() {
+    let options = PlotOptions {
+        color_by_strand: true,
+        ..PlotOptions::default()
+    };
+
+    let specs = manipulate_json(test_data(), 0, 10, &read_specs(None).unwrap(), &options);
+    assert_eq!(specs["signals"][0]["name"], "color_by_strand");
+    assert_eq!(specs["signals"][0]["value"], true);
+
+    let specs = vega_lite_spec(test_data(), 0, 10, &options);
+    let calculate = specs["transform"][2]["calculate"].as_str().unwrap();
+    assert!(calculate.contains("datum.strand + 'Strand'"));
+}
+
+#[test]
+fn compact_json_test() {
+    let mut readers = Readers::open(
+        Path::new("tests/resources/ref.fa"),
+        Path::new("tests/resources/test.bam"),
+        Path::new("tests/resources/report-test.vcf.gz"),
+    )
+    .unwrap();
+    let data = StaticData::fetch(
+        &mut readers,
+        String::from("chr1"),
+        0,
+        100,
+        &ReadOrder::default(),
+        None,
+        0,
+    )
+    .unwrap();
+    let specs = compact_json(
+        &data,
+        0,
+        100,
+        &read_specs(None).unwrap(),
+        &PlotOptions::default(),
+    );
+
+    assert_eq!(specs["data"][1]["name"], "reads");
+    assert_eq!(specs["data"][1]["values"][0]["name"], data.matches[0].name);
+    assert_eq!(specs["data"][1]["values"][0]["strand"], "Forward");
+    assert_eq!(specs["data"][1]["values"][0]["allele"], "Ref");
+    assert_eq!(specs["data"][1]["values"][0]["pair_class"], "Normal");
+    assert_eq!(specs["data"][2]["name"], "fasta");
+    assert_eq!(specs["data"][2]["transform"][0]["from"], "reads");
+    assert_eq!(
+        specs["data"][2]["transform"][0]["values"],
+        json!(["name", "flags", "strand", "pair_class", "allele"])
+    );
+
+    let values = specs["data"][2]["values"].as_array().unwrap();
+    assert_eq!(
+        values.len(),
+        100 + data.variants.len() + data.matches.len() + data.bases.len()
+    );
+    assert!(values.iter().all(|v| v.get("name").is_none()));
+}
+
+#[test]
+fn row_spacing_test() {
+    assert_eq!(parse_row_spacing("0.2"), Ok(0.2));
+    assert!(parse_row_spacing("1.5").is_err());
+    assert!(parse_row_spacing("-0.1").is_err());
+    assert!(parse_row_spacing("wide").is_err());
+
+    let options = PlotOptions {
+        row_spacing: Some(2.0),
+        ..PlotOptions::default()
+    };
+    assert!(options.validate().is_err());
+    assert!(PlotOptions::default().validate().is_ok());
+}
@@ -0,0 +1,333 @@
+use alignment_reader::{AlignmentMatch, AlignmentNucleobase, Marker};
+use grouping::{GroupKey, GroupSeparator, ReadInfo, ReadInfos, ReadOrder};
+use lru::LruCache;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::iter;
+use std::sync::Mutex;
+
+// Number of placed reads a layout remembers before it forgets the reads outside of the window
+const MAX_LAYOUT_READS: usize = 100_000;
+
+/// Packs reads into rows without overlaps. Once a read has been placed it keeps its row, so the
+/// rows stay the same when adjacent windows are requested one after another. When more than
+/// `max_reads` reads have been placed, only the reads of the last window are kept.
+pub struct RowLayout {
+    read_rows: HashMap<String, u32>,
+    // Extent (start, end) of every placed read, as it was occupied in its row
+    extents: HashMap<String, (u32, u32)>,
+    // Occupied intervals (start -> end) of every row, starting with row 1
+    rows: Vec<BTreeMap<u32, u32>>,
+    max_reads: usize,
+}
+
+impl RowLayout {
+    pub fn new() -> RowLayout {
+        RowLayout {
+            read_rows: HashMap::new(),
+            extents: HashMap::new(),
+            rows: Vec::new(),
+            max_reads: MAX_LAYOUT_READS,
+        }
+    }
+
+    pub fn with_max_reads(mut self, max_reads: usize) -> RowLayout {
+        self.max_reads = max_reads;
+        self
+    }
+
+    /// Sets the row of all bases and matches. Reads that were not seen before are placed in
+    /// the order of their start positions, in the first row with enough space.
+    ///
+    /// The extent of a read can differ between windows, e.g. when the end of its mate is only
+    /// known once the mate has been fetched as well. Such reads keep their row if it has space
+    /// for the new extent, otherwise they are placed again.
+    pub fn assign(&mut self, bases: &mut [AlignmentNucleobase], matches: &mut [AlignmentMatch]) {
+        let mut new_reads = Vec::new();
+        let mut changed_reads = Vec::new();
+        for (name, (start, end)) in read_extents(bases, matches) {
+            match self.extents.get(name) {
+                None => new_reads.push((start, end, name)),
+                Some(&extent) if extent != (start, end) => {
+                    self.rows[self.read_rows[name] as usize - 1].remove(&extent.0);
+                    changed_reads.push((start, end, name));
+                }
+                Some(_) => {}
+            }
+        }
+
+        changed_reads.sort();
+        for (start, end, name) in changed_reads {
+            let row = self.read_rows[name];
+            if is_free(&self.rows[row as usize - 1], start, end) {
+                self.rows[row as usize - 1].insert(start, end);
+                self.extents.insert(name.to_owned(), (start, end));
+            } else {
+                new_reads.push((start, end, name));
+            }
+        }
+
+        new_reads.sort();
+        for (start, end, name) in new_reads {
+            let row = self.place(start, end);
+            self.read_rows.insert(name.to_owned(), row);
+            self.extents.insert(name.to_owned(), (start, end));
+        }
+
+        set_rows(&self.read_rows, bases, matches);
+
+        if self.read_rows.len() > self.max_reads {
+            self.retain(read_extents(bases, matches));
+        }
+    }
+
+    // Forgets all reads except the given ones, which keep their rows
+    fn retain(&mut self, reads: HashMap<&str, (u32, u32)>) {
+        let mut read_rows = HashMap::new();
+        let mut extents = HashMap::new();
+        let mut rows = vec![BTreeMap::new(); self.rows.len()];
+
+        for (name, (start, end)) in reads {
+            let row = self.read_rows[name];
+            rows[row as usize - 1].insert(start, end);
+            read_rows.insert(name.to_owned(), row);
+            extents.insert(name.to_owned(), (start, end));
+        }
+        while rows.last().map_or(false, |row| row.is_empty()) {
+            rows.pop();
+        }
+
+        self.read_rows = read_rows;
+        self.extents = extents;
+        self.rows = rows;
+    }
+
+    fn place(&mut self, start: u32, end: u32) -> u32 {
+        for (i, row) in self.rows.iter_mut().enumerate() {
+            if is_free(row, start, end) {
+                row.insert(start, end);
+                return i as u32 + 1;
+            }
+        }
+
+        let mut row = BTreeMap::new();
+        row.insert(start, end);
+        self.rows.push(row);
+
+        self.rows.len() as u32
+    }
+}
+
+// Whether the interval from `start` to `end` does not overlap any interval of the row
+fn is_free(row: &BTreeMap<u32, u32>, start: u32, end: u32) -> bool {
+    // Intervals of a row do not overlap, so only the last one starting before `end` can
+    match row.range(..=end).next_back() {
+        Some((_, row_end)) => *row_end < start,
+        None => true,
+    }
+}
+
+/// Places the reads of a region in groups that each start with a separator row. Within a group,
+/// reads are sorted by their base at the sort position if there is one, otherwise they are
+/// placed like in `RowLayout`. The rows only fit the given region, so they change while
+/// scrolling.
+pub fn assign_grouped(
+    bases: &mut [AlignmentNucleobase],
+    matches: &mut [AlignmentMatch],
+    infos: &ReadInfos,
+    order: &ReadOrder,
+) -> Vec<GroupSeparator> {
+    let base_ranks = match order.sort_by_base {
+        Some(position) => base_ranks(bases, matches, position),
+        None => HashMap::new(),
+    };
+
+    let mut groups: BTreeMap<Option<GroupKey>, Vec<(u8, u32, u32, String)>> = BTreeMap::new();
+    for (name, (start, end)) in read_extents(bases, matches) {
+        let key = order
+            .group_by
+            .map(|group_by| group_by.key(infos.get(name).unwrap_or(&ReadInfo::default())));
+        // Reads that do not cover the sort position come last
+        let rank = base_ranks.get(name).cloned().unwrap_or(u8::max_value());
+        groups
+            .entry(key)
+            .or_insert_with(Vec::new)
+            .push((rank, start, end, name.to_owned()));
+    }
+
+    let mut read_rows = HashMap::new();
+    let mut separators = Vec::new();
+    let mut next_row = 1;
+    for (key, mut reads) in groups {
+        if let Some(key) = key {
+            separators.push(GroupSeparator {
+                group: key.label,
+                row: next_row,
+            });
+            next_row += 1;
+        }
+
+        reads.sort();
+        let mut layout = RowLayout::new();
+        for (_, start, end, name) in reads {
+            let row = layout.place(start, end);
+            read_rows.insert(name, next_row + row - 1);
+        }
+        next_row += layout.rows.len() as u32;
+    }
+
+    set_rows(&read_rows, bases, matches);
+
+    separators
+}
+
+// Rank of the base of every read at a position: mismatches and deletions first, then reads
+// that match the reference
+fn base_ranks<'a>(
+    bases: &'a [AlignmentNucleobase],
+    matches: &'a [AlignmentMatch],
+    position: u64,
+) -> HashMap<&'a str, u8> {
+    let position = position as f64;
+    let covers = |start: f64, end: f64| start < position && position < end;
+    let mut ranks = HashMap::new();
+
+    for m in matches {
+        if m.marker_type == Marker::Match && covers(m.start_position, m.end_position) {
+            ranks.insert(&m.name[..], 6);
+        }
+    }
+    for b in bases {
+        let rank = match b.marker_type {
+            Marker::A => 0,
+            Marker::C => 1,
+            Marker::G => 2,
+            Marker::T => 3,
+            Marker::Deletion => 5,
+            ref m if m.is_ambiguous() => 4,
+            _ => continue,
+        };
+        if covers(b.start_position, b.end_position) {
+            ranks.insert(&b.name[..], rank);
+        }
+    }
+
+    ranks
+}
+
+// Start and end of every read, over all its bases and matches
+fn read_extents<'a>(
+    bases: &'a [AlignmentNucleobase],
+    matches: &'a [AlignmentMatch],
+) -> HashMap<&'a str, (u32, u32)> {
+    let mut extents: HashMap<&str, (u32, u32)> = HashMap::new();
+    let reads = matches
+        .iter()
+        .map(|m| (&m.name, m.read_start, m.read_end))
+        .chain(bases.iter().map(|b| (&b.name, b.read_start, b.read_end)));
+
+    for (name, start, end) in reads {
+        let extent = extents.entry(name).or_insert((start, end));
+        extent.0 = extent.0.min(start);
+        extent.1 = extent.1.max(end);
+    }
+
+    extents
+}
+
+fn set_rows(
+    read_rows: &HashMap<String, u32>,
+    bases: &mut [AlignmentNucleobase],
+    matches: &mut [AlignmentMatch],
+) {
+    for m in matches.iter_mut() {
+        m.row = read_rows[&m.name];
+    }
+    for b in bases.iter_mut() {
+        b.row = read_rows[&b.name];
+    }
+}
+
+/// Numbers the rows from 1 again without the rows that were left empty, e.g. after reads were
+/// filtered out. Separators of groups without any reads are removed.
+pub fn compact_rows(read_rows: Vec<&mut u32>, groups: &mut Vec<GroupSeparator>) {
+    let used: BTreeSet<u32> = read_rows.iter().map(|row| **row).collect();
+
+    // A group ends where the next one starts
+    let ends: Vec<u32> = groups
+        .iter()
+        .skip(1)
+        .map(|g| g.row)
+        .chain(iter::once(u32::max_value()))
+        .collect();
+    let kept = groups
+        .drain(..)
+        .zip(ends)
+        .filter(|(g, end)| used.range(g.row + 1..*end).next().is_some())
+        .map(|(g, _)| g)
+        .collect();
+    *groups = kept;
+
+    let rows: BTreeSet<u32> = used
+        .into_iter()
+        .chain(groups.iter().map(|g| g.row))
+        .collect();
+    let new_rows: HashMap<u32, u32> = rows.into_iter().zip(1..).collect();
+
+    for row in read_rows {
+        *row = new_rows[&*row];
+    }
+    for g in groups.iter_mut() {
+        g.row = new_rows[&g.row];
+    }
+}
+
+/// The row layouts of the most recently viewed chromosomes of a dataset, one per viewer session,
+/// so that the row of a read only depends on the windows that the same viewer has requested.
+pub struct Layouts {
+    sessions: Mutex<LruCache<(String, String), RowLayout>>,
+}
+
+impl Layouts {
+    pub fn new(capacity: usize) -> Layouts {
+        Layouts {
+            sessions: Mutex::new(LruCache::new(capacity)),
+        }
+    }
+
+    pub fn assign(
+        &self,
+        session: &str,
+        chromosome: &str,
+        bases: &mut [AlignmentNucleobase],
+        matches: &mut [AlignmentMatch],
+    ) {
+        let key = (session.to_owned(), chromosome.to_owned());
+        let mut sessions = self.sessions.lock().unwrap();
+        if !sessions.contains(&key) {
+            sessions.put(key.clone(), RowLayout::new());
+        }
+        sessions.get_mut(&key).unwrap().assign(bases, matches);
+    }
+
+    /// Uses the layout of the session and chromosome for the default order. Without a session,
+    /// or when reads are grouped or sorted, the layout only covers the requested region.
+    pub fn assign_ordered(
+        &self,
+        session: Option<&str>,
+        chromosome: &str,
+        bases: &mut [AlignmentNucleobase],
+        matches: &mut [AlignmentMatch],
+        infos: &ReadInfos,
+        order: &ReadOrder,
+    ) -> Vec<GroupSeparator> {
+        if !order.is_default() {
+            return assign_grouped(bases, matches, infos, order);
+        }
+
+        match session {
+            Some(session) => self.assign(session, chromosome, bases, matches),
+            None => RowLayout::new().assign(bases, matches),
+        }
+        Vec::new()
+    }
+}
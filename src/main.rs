@@ -8,114 +8,658 @@ extern crate serde;
 extern crate serde_json;
 #[macro_use]
 extern crate log;
+#[macro_use]
+extern crate lazy_static;
 
+extern crate base64;
+extern crate bcrypt;
 extern crate bio;
 extern crate bit_vec;
 extern crate clap;
+extern crate lru;
 extern crate regex;
+extern crate rmp_serde;
 extern crate rocket_contrib;
 extern crate rust_htslib;
 extern crate rustc_serialize;
 extern crate tera;
+extern crate toml;
 
+mod access;
 mod alignment_reader;
+mod allele_support;
+mod assets;
+mod auth;
+mod aux_tags;
+mod cache;
+mod catalogue;
+mod columnar;
+mod encoding;
 mod fasta_reader;
+mod grouping;
 mod json_generator;
+mod layout;
+mod modifications;
+mod name_index;
+mod overview;
+mod pairs;
+mod read_details;
+mod readers;
+mod region;
+mod region_data;
 mod report;
 mod static_reader;
+mod svg_renderer;
+mod theme;
+mod tls;
 mod variant_reader;
 
+#[cfg(test)]
+mod access_tests;
 #[cfg(test)]
 mod alignment_tests;
 #[cfg(test)]
+mod allele_support_tests;
+#[cfg(test)]
+mod assets_tests;
+#[cfg(test)]
+mod cache_tests;
+#[cfg(test)]
+mod encoding_tests;
+#[cfg(test)]
+mod grouping_tests;
+#[cfg(test)]
+mod json_tests;
+#[cfg(test)]
+mod pairs_tests;
+#[cfg(test)]
 mod reference_tests;
 #[cfg(test)]
+mod region_tests;
+#[cfg(test)]
 mod report_tests;
 #[cfg(test)]
+mod svg_tests;
+#[cfg(test)]
+mod tls_tests;
+#[cfg(test)]
 mod variant_tests;
 
-use alignment_reader::{get_reads, AlignmentMatch, AlignmentNucleobase};
+use access::Access;
+use alignment_reader::{
+    hide_small_indels, hide_soft_clips, keep_alt_reads, AlignmentMatch, AlignmentNucleobase,
+};
+use assets::{normalize_prefix, read_asset, StaticDir};
+use auth::{Challenge, User};
+use catalogue::{Dataset, DatasetConfig, DatasetInfo, Datasets, DEFAULT_DATASET};
 use clap::{App, Arg, ArgMatches, SubCommand};
-use fasta_reader::{read_fasta, Nucleobase};
-use json_generator::{create_data, manipulate_json};
+use columnar::ColumnarAlignments;
+use encoding::{Encoded, Encoding};
+use fasta_reader::Nucleobase;
+use grouping::{GroupBy, GroupSeparator, ReadOrder};
+use json_generator::{compact_json, create_data, manipulate_json, read_specs, vega_lite_spec};
+use modifications::{select_modifications, ModificationType};
+use name_index::Locus;
+use overview::LevelOfDetail;
+use pairs::{InsertSizeRange, INSERT_SIZE_SAMPLE};
+use read_details::{fetch_read_details, ReadDetails};
+use readers::Readers;
+use region::{read_bed, Region, UnknownChromosome};
+use region_data::{RegionData, Tracks};
 use report::make_report;
+use rocket::config::{Config, ConfigBuilder, Environment};
+use rocket::http::uri::Origin;
+use rocket::http::{ContentType, Status};
+use rocket::response::content::{Content, Html};
+use rocket::response::status::Custom;
+use rocket::response::Redirect;
 use rocket::State;
 use rocket_contrib::compression::Compression;
 use rocket_contrib::json::Json;
-use rocket_contrib::serve::StaticFiles;
-use rocket_contrib::templates::Template;
-use std::collections::HashMap;
+use static_reader::StaticData;
 use std::error::Error;
+use std::fs;
 use std::io::{self, stdout, Write};
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::str::FromStr;
+use std::sync::Arc;
+use svg_renderer::render_svg;
 use tera::{Context, Tera};
-use variant_reader::{read_indexed_vcf, Variant};
+use theme::{parse_row_spacing, PlotOptions, Theme};
+use tls::check_pem_files;
+use variant_reader::Variant;
+
+fn open_dataset(
+    datasets: &Datasets,
+    user: &User,
+    name: &str,
+) -> Result<Arc<Dataset>, Custom<String>> {
+    if !user.may_access(name) {
+        return Err(Custom(
+            Status::Forbidden,
+            format!("no access to dataset '{}'", name),
+        ));
+    }
+    datasets
+        .get(name)
+        .ok_or_else(|| Custom(Status::NotFound, format!("unknown dataset '{}'", name)))
+}
+
+/// Answers requests for chromosomes that the files of a dataset don't contain with 404, and other
+/// failed reads with 500.
+fn fetch_error(e: Box<dyn Error>) -> Custom<String> {
+    let status = if e.is::<UnknownChromosome>() {
+        Status::NotFound
+    } else {
+        Status::InternalServerError
+    };
+    Custom(status, e.to_string())
+}
+
+/// Parses the grouping and sorting parameters of a request.
+fn read_order(
+    group_by: Option<String>,
+    sort_by_base: Option<u64>,
+) -> Result<ReadOrder, Custom<String>> {
+    let group_by = match group_by {
+        Some(group_by) => {
+            Some(GroupBy::from_str(&group_by).map_err(|e| Custom(Status::BadRequest, e))?)
+        }
+        None => None,
+    };
+
+    Ok(ReadOrder {
+        group_by: group_by,
+        sort_by_base: sort_by_base,
+    })
+}
+
+/// Parses the base modification that reads are coloured by.
+fn modification_type(
+    modification: Option<String>,
+) -> Result<Option<ModificationType>, Custom<String>> {
+    match modification {
+        Some(modification) => Ok(Some(
+            ModificationType::from_str(&modification).map_err(|e| Custom(Status::BadRequest, e))?,
+        )),
+        None => Ok(None),
+    }
+}
+
+#[get("/datasets")]
+fn datasets(datasets: State<Datasets>, user: User) -> Json<Vec<DatasetInfo>> {
+    Json(
+        datasets
+            .list()
+            .into_iter()
+            .filter(|dataset| user.may_access(&dataset.name))
+            .collect(),
+    )
+}
 
 #[get("/reference/<chromosome>/<from>/<to>")]
 fn reference(
-    params: State<ArgMatches>,
+    datasets: State<Datasets>,
+    user: User,
     chromosome: String,
     from: u64,
     to: u64,
-) -> Json<Vec<Nucleobase>> {
-    let response = read_fasta(
-        Path::new(params.value_of("fasta file").unwrap()),
+) -> Result<Json<Vec<Nucleobase>>, Custom<String>> {
+    dataset_reference(
+        datasets,
+        user,
+        DEFAULT_DATASET.to_owned(),
         chromosome,
         from,
         to,
-    );
-    Json(response)
+    )
+}
+
+#[get("/<dataset>/reference/<chromosome>/<from>/<to>")]
+fn dataset_reference(
+    datasets: State<Datasets>,
+    user: User,
+    dataset: String,
+    chromosome: String,
+    from: u64,
+    to: u64,
+) -> Result<Json<Vec<Nucleobase>>, Custom<String>> {
+    let dataset = open_dataset(&datasets, &user, &dataset)?;
+    let response = dataset
+        .caches
+        .reference(&dataset.pool, &Region::new(chromosome, from, to))
+        .map_err(fetch_error)?;
+    Ok(Json(response))
 }
 
-#[get("/alignment/<chromosome>/<from>/<to>")]
+#[get("/alignment/<chromosome>/<from>/<to>?<group_by>&<sort_by_base>&<soft_clips>&<min_indel_length>&<alt_only>&<modification>&<session>")]
 fn alignment(
-    params: State<ArgMatches>,
+    datasets: State<Datasets>,
+    user: User,
+    level_of_detail: State<LevelOfDetail>,
+    encoding: Encoding,
     chromosome: String,
     from: u64,
     to: u64,
-) -> Json<(Vec<AlignmentNucleobase>, Vec<AlignmentMatch>)> {
-    let response = get_reads(
-        Path::new(params.value_of("bam file").unwrap()),
-        Path::new(params.value_of("fasta file").unwrap()),
+    group_by: Option<String>,
+    sort_by_base: Option<u64>,
+    soft_clips: Option<bool>,
+    min_indel_length: Option<u32>,
+    alt_only: Option<bool>,
+    modification: Option<String>,
+    session: Option<String>,
+) -> Result<
+    Encoded<
+        (
+            Vec<AlignmentNucleobase>,
+            Vec<AlignmentMatch>,
+            Vec<GroupSeparator>,
+        ),
+        (ColumnarAlignments, Vec<GroupSeparator>),
+    >,
+    Custom<String>,
+> {
+    dataset_alignment(
+        datasets,
+        user,
+        level_of_detail,
+        encoding,
+        DEFAULT_DATASET.to_owned(),
         chromosome,
         from,
         to,
+        group_by,
+        sort_by_base,
+        soft_clips,
+        min_indel_length,
+        alt_only,
+        modification,
+        session,
+    )
+}
+
+#[get("/<dataset>/alignment/<chromosome>/<from>/<to>?<group_by>&<sort_by_base>&<soft_clips>&<min_indel_length>&<alt_only>&<modification>&<session>")]
+fn dataset_alignment(
+    datasets: State<Datasets>,
+    user: User,
+    level_of_detail: State<LevelOfDetail>,
+    encoding: Encoding,
+    dataset: String,
+    chromosome: String,
+    from: u64,
+    to: u64,
+    group_by: Option<String>,
+    sort_by_base: Option<u64>,
+    soft_clips: Option<bool>,
+    min_indel_length: Option<u32>,
+    alt_only: Option<bool>,
+    modification: Option<String>,
+    session: Option<String>,
+) -> Result<
+    Encoded<
+        (
+            Vec<AlignmentNucleobase>,
+            Vec<AlignmentMatch>,
+            Vec<GroupSeparator>,
+        ),
+        (ColumnarAlignments, Vec<GroupSeparator>),
+    >,
+    Custom<String>,
+> {
+    let dataset = open_dataset(&datasets, &user, &dataset)?;
+    let order = read_order(group_by, sort_by_base)?;
+    let modification = modification_type(modification)?;
+    let region = Region::new(chromosome, from, to);
+    level_of_detail
+        .check_detail_span(region.from, region.to)
+        .map_err(|e| Custom(Status::BadRequest, e))?;
+    let (mut bases, mut matches, infos) = dataset
+        .caches
+        .alignments(&dataset.pool, &region)
+        .map_err(fetch_error)?;
+    let mut groups = dataset.layouts.assign_ordered(
+        session.as_ref().map(|s| s.as_str()),
+        &region.chromosome,
+        &mut bases,
+        &mut matches,
+        &infos,
+        &order,
     );
-    Json(response)
+    if !soft_clips.unwrap_or(true) {
+        hide_soft_clips(&mut bases);
+    }
+    if let Some(min_length) = min_indel_length {
+        hide_small_indels(&mut bases, &mut matches, min_length);
+    }
+    if alt_only.unwrap_or(false) {
+        keep_alt_reads(&mut bases, &mut matches, &mut groups, &order);
+    }
+    select_modifications(&mut bases, modification);
+
+    if encoding.msgpack {
+        Ok(Encoded::MsgPack((
+            ColumnarAlignments::new(&bases, &matches),
+            groups,
+        )))
+    } else {
+        Ok(Encoded::Json(Json((bases, matches, groups))))
+    }
 }
 
 #[get("/variant/<chromosome>/<from>/<to>")]
 fn variant(
-    params: State<ArgMatches>,
+    datasets: State<Datasets>,
+    user: User,
     chromosome: String,
     from: u64,
     to: u64,
-) -> Json<Vec<Variant>> {
-    let response = read_indexed_vcf(
-        Path::new(params.value_of("vcf file").unwrap()),
+) -> Result<Json<Vec<Variant>>, Custom<String>> {
+    dataset_variant(
+        datasets,
+        user,
+        DEFAULT_DATASET.to_owned(),
         chromosome,
         from,
         to,
-    );
-    Json(response)
+    )
+}
+
+#[get("/<dataset>/variant/<chromosome>/<from>/<to>")]
+fn dataset_variant(
+    datasets: State<Datasets>,
+    user: User,
+    dataset: String,
+    chromosome: String,
+    from: u64,
+    to: u64,
+) -> Result<Json<Vec<Variant>>, Custom<String>> {
+    let dataset = open_dataset(&datasets, &user, &dataset)?;
+    let response = dataset
+        .caches
+        .variants(&dataset.pool, &Region::new(chromosome, from, to))
+        .map_err(fetch_error)?;
+    Ok(Json(response))
+}
+
+#[get("/region/<chromosome>/<from>/<to>?<tracks>&<group_by>&<sort_by_base>&<soft_clips>&<min_indel_length>&<alt_only>&<modification>&<session>")]
+fn region(
+    datasets: State<Datasets>,
+    user: User,
+    level_of_detail: State<LevelOfDetail>,
+    encoding: Encoding,
+    chromosome: String,
+    from: u64,
+    to: u64,
+    tracks: Option<String>,
+    group_by: Option<String>,
+    sort_by_base: Option<u64>,
+    soft_clips: Option<bool>,
+    min_indel_length: Option<u32>,
+    alt_only: Option<bool>,
+    modification: Option<String>,
+    session: Option<String>,
+) -> Result<Encoded<RegionData, RegionData>, Custom<String>> {
+    dataset_region(
+        datasets,
+        user,
+        level_of_detail,
+        encoding,
+        DEFAULT_DATASET.to_owned(),
+        chromosome,
+        from,
+        to,
+        tracks,
+        group_by,
+        sort_by_base,
+        soft_clips,
+        min_indel_length,
+        alt_only,
+        modification,
+        session,
+    )
+}
+
+#[get("/<dataset>/region/<chromosome>/<from>/<to>?<tracks>&<group_by>&<sort_by_base>&<soft_clips>&<min_indel_length>&<alt_only>&<modification>&<session>")]
+fn dataset_region(
+    datasets: State<Datasets>,
+    user: User,
+    level_of_detail: State<LevelOfDetail>,
+    encoding: Encoding,
+    dataset: String,
+    chromosome: String,
+    from: u64,
+    to: u64,
+    tracks: Option<String>,
+    group_by: Option<String>,
+    sort_by_base: Option<u64>,
+    soft_clips: Option<bool>,
+    min_indel_length: Option<u32>,
+    alt_only: Option<bool>,
+    modification: Option<String>,
+    session: Option<String>,
+) -> Result<Encoded<RegionData, RegionData>, Custom<String>> {
+    let dataset = open_dataset(&datasets, &user, &dataset)?;
+    let tracks = match tracks {
+        Some(tracks) => Tracks::from_str(&tracks).map_err(|e| Custom(Status::BadRequest, e))?,
+        None => dataset.tracks,
+    };
+    let order = read_order(group_by, sort_by_base)?;
+    let modification = modification_type(modification)?;
+    let mut response = RegionData::fetch(
+        &dataset.pool,
+        &dataset.caches,
+        &dataset.layouts,
+        session.as_ref().map(|s| s.as_str()),
+        *level_of_detail,
+        &Region::new(chromosome, from, to),
+        tracks,
+        &order,
+    )
+    .map_err(fetch_error)?;
+    if !soft_clips.unwrap_or(true) {
+        response.hide_soft_clips();
+    }
+    if let Some(min_length) = min_indel_length {
+        response.hide_small_indels(min_length);
+    }
+    if alt_only.unwrap_or(false) {
+        response.keep_alt_reads(&order);
+    }
+    response.select_modifications(modification);
+
+    if encoding.msgpack {
+        Ok(Encoded::MsgPack(response.into_columnar()))
+    } else {
+        Ok(Encoded::Json(Json(response)))
+    }
+}
+
+#[get("/search?<q>")]
+fn search(
+    datasets: State<Datasets>,
+    user: User,
+    q: String,
+) -> Result<Json<Vec<Locus>>, Custom<String>> {
+    dataset_search(datasets, user, DEFAULT_DATASET.to_owned(), q)
+}
+
+// Ranked after `/read/<name>`, which has the same number of segments
+#[get("/<dataset>/search?<q>", rank = 1)]
+fn dataset_search(
+    datasets: State<Datasets>,
+    user: User,
+    dataset: String,
+    q: String,
+) -> Result<Json<Vec<Locus>>, Custom<String>> {
+    let dataset = open_dataset(&datasets, &user, &dataset)?;
+    Ok(Json(dataset.name_index.search(&q)))
+}
+
+#[get("/read/<name>?<chromosome>&<from>&<to>")]
+fn read(
+    datasets: State<Datasets>,
+    user: User,
+    name: String,
+    chromosome: String,
+    from: u64,
+    to: u64,
+) -> Result<Json<Vec<ReadDetails>>, Custom<String>> {
+    dataset_read(
+        datasets,
+        user,
+        DEFAULT_DATASET.to_owned(),
+        name,
+        chromosome,
+        from,
+        to,
+    )
+}
+
+#[get("/<dataset>/read/<name>?<chromosome>&<from>&<to>")]
+fn dataset_read(
+    datasets: State<Datasets>,
+    user: User,
+    dataset: String,
+    name: String,
+    chromosome: String,
+    from: u64,
+    to: u64,
+) -> Result<Json<Vec<ReadDetails>>, Custom<String>> {
+    let dataset = open_dataset(&datasets, &user, &dataset)?;
+    let mut readers = dataset.pool.get().map_err(fetch_error)?;
+    let details =
+        fetch_read_details(&mut readers.bam, chromosome, from, to, &name).map_err(fetch_error)?;
+    Ok(Json(details))
 }
 
 #[get("/")]
-fn index(params: State<ArgMatches>) -> Template {
-    let mut context = HashMap::new();
+fn index(params: State<ArgMatches>) -> Result<Html<String>, Status> {
+    render_report(&params).map(Html).map_err(|e| {
+        error!("Could not render the report: {}", e);
+        Status::InternalServerError
+    })
+}
+
+// The viewer loads its assets and the api with relative URLs, which only resolve below the
+// prefix if it ends with a slash
+#[get("/", rank = 21)]
+fn home(
+    static_dir: State<StaticDir>,
+    user: User,
+    uri: &Origin,
+) -> Result<Option<Content<Vec<u8>>>, Redirect> {
+    if !uri.path().ends_with('/') {
+        return Err(Redirect::permanent(format!("{}/", uri.path())));
+    }
+
+    Ok(static_asset(static_dir, user, PathBuf::from("index.html")))
+}
+
+#[get("/<path..>", rank = 20)]
+fn static_asset(
+    static_dir: State<StaticDir>,
+    _user: User,
+    path: PathBuf,
+) -> Option<Content<Vec<u8>>> {
+    let content = read_asset(static_dir.0.as_ref().map(|d| d.as_path()), &path)?.into_owned();
+    let content_type = path
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .and_then(ContentType::from_extension)
+        .unwrap_or(ContentType::Binary);
+
+    Some(Content(content_type, content))
+}
+
+#[catch(401)]
+fn unauthorized() -> Challenge {
+    Challenge
+}
+
+fn with_tls(config: ConfigBuilder, params: &ArgMatches) -> Result<ConfigBuilder, Box<dyn Error>> {
+    match (params.value_of("tls certs"), params.value_of("tls key")) {
+        (Some(certs), Some(key)) => {
+            check_pem_files(Path::new(certs), Path::new(key))?;
+            Ok(config.tls(certs, key))
+        }
+        _ => Ok(config),
+    }
+}
+
+fn render_static(
+    readers: &mut Readers,
+    region: &Region,
+    format: &str,
+    specs: &str,
+    options: &PlotOptions,
+    order: &ReadOrder,
+    insert_sizes: Option<&InsertSizeRange>,
+) -> Result<String, Box<dyn Error>> {
+    let mut data = StaticData::fetch(
+        readers,
+        region.chromosome.clone(),
+        region.from,
+        region.to,
+        order,
+        insert_sizes,
+        options.min_indel_length,
+    )?;
+    if !options.soft_clips {
+        data.hide_soft_clips();
+    }
+    if options.alt_only {
+        data.keep_alt_reads();
+    }
+    data.select_modifications(options.modification);
+
+    Ok(match format {
+        "svg" => render_svg(&data, region, options),
+        "vega-lite" => {
+            vega_lite_spec(create_data(&data), region.from, region.to, options).to_string()
+        }
+        "compact-json" => compact_json(&data, region.from, region.to, specs, options).to_string(),
+        _ => {
+            manipulate_json(create_data(&data), region.from, region.to, specs, options).to_string()
+        }
+    })
+}
+
+fn file_extension(format: &str) -> &str {
+    match format {
+        "svg" => "svg",
+        "vega-lite" => "vl.json",
+        _ => "json",
+    }
+}
+
+fn render_report(params: &ArgMatches) -> Result<String, Box<dyn Error>> {
+    let specs = read_specs(params.value_of("spec").map(Path::new))?;
+
+    let mut templates = Tera::default();
+    templates.add_raw_template(
+        "report.html.tera",
+        include_str!("../templates/report.html.tera"),
+    )?;
+    let mut context = Context::new();
     context.insert(
         "variants",
-        make_report(
+        &make_report(
             Path::new(params.value_of("vcf file").unwrap()),
             Path::new(params.value_of("fasta file").unwrap()),
             Path::new(params.value_of("bam file").unwrap()),
             params.value_of("chromosome").unwrap().parse().unwrap(),
-        )
-        .unwrap(),
+            &specs,
+            &ReadOrder {
+                group_by: match params.value_of("group by") {
+                    Some(group_by) => Some(GroupBy::from_str(group_by)?),
+                    None => None,
+                },
+                sort_by_base: None,
+            },
+        )?,
     );
 
-    Template::render("report", &context)
+    Ok(templates.render("report.html.tera", &context)?)
 }
 
 fn main() -> Result<(), Box<dyn Error>> {
@@ -130,21 +674,110 @@ fn main() -> Result<(), Box<dyn Error>> {
                 .author("Felix W. <fxwiegand@wgdnet.de>")
                 .arg(
                     Arg::with_name("bam file")
-                        .required(true)
+                        .required_unless("catalogue")
                         .help("your input bam file")
                         .index(1),
                 )
                 .arg(
                     Arg::with_name("fasta file")
-                        .required(true)
+                        .required_unless("catalogue")
                         .help("your input fasta file")
                         .index(2),
                 )
                 .arg(
                     Arg::with_name("vcf file")
-                        .required(true)
+                        .required_unless("catalogue")
                         .help("your input vcf file")
                         .index(3),
+                )
+                .arg(
+                    Arg::with_name("annotation")
+                        .long("annotation")
+                        .takes_value(true)
+                        .conflicts_with("catalogue")
+                        .help("gff, gtf or bed file with gene names to search for"),
+                )
+                .arg(
+                    Arg::with_name("catalogue")
+                        .long("catalogue")
+                        .takes_value(true)
+                        .conflicts_with_all(&["bam file", "fasta file", "vcf file"])
+                        .help("toml file with several named datasets to serve, reloaded when it changes"),
+                )
+                .arg(
+                    Arg::with_name("insert size")
+                        .long("insert-size")
+                        .takes_value(true)
+                        .conflicts_with("catalogue")
+                        .help("expected insert sizes like 200-600, estimated from the bam file if not given"),
+                )
+                .arg(
+                    Arg::with_name("access")
+                        .long("access")
+                        .takes_value(true)
+                        .help("toml file with users, tokens and the datasets they may see"),
+                )
+                .arg(
+                    Arg::with_name("cache size")
+                        .long("cache-size")
+                        .takes_value(true)
+                        .default_value("128")
+                        .help("number of recently requested regions kept in memory per track"),
+                )
+                .arg(
+                    Arg::with_name("max detail span")
+                        .long("max-detail-span")
+                        .takes_value(true)
+                        .default_value("10000")
+                        .help("largest region in bp that is shown with single reads, larger regions are shown as an overview"),
+                )
+                .arg(
+                    Arg::with_name("overview bins")
+                        .long("overview-bins")
+                        .takes_value(true)
+                        .default_value("500")
+                        .help("number of bins of the overview"),
+                )
+                .arg(
+                    Arg::with_name("address")
+                        .long("address")
+                        .takes_value(true)
+                        .default_value("localhost")
+                        .help("address the server listens on"),
+                )
+                .arg(
+                    Arg::with_name("port")
+                        .long("port")
+                        .takes_value(true)
+                        .default_value("8000")
+                        .help("port the server listens on"),
+                )
+                .arg(
+                    Arg::with_name("url prefix")
+                        .long("url-prefix")
+                        .takes_value(true)
+                        .default_value("")
+                        .help("path under which the viewer is served, e.g. /genomes behind a reverse proxy"),
+                )
+                .arg(
+                    Arg::with_name("static dir")
+                        .long("static-dir")
+                        .takes_value(true)
+                        .help("directory with web assets that are served instead of the built-in ones"),
+                )
+                .arg(
+                    Arg::with_name("tls certs")
+                        .long("tls-certs")
+                        .takes_value(true)
+                        .requires("tls key")
+                        .help("PEM file with the certificate chain, to serve https instead of http"),
+                )
+                .arg(
+                    Arg::with_name("tls key")
+                        .long("tls-key")
+                        .takes_value(true)
+                        .requires("tls certs")
+                        .help("PEM file with the private key of the certificate"),
                 ),
         )
         .subcommand(
@@ -172,21 +805,118 @@ fn main() -> Result<(), Box<dyn Error>> {
                 )
                 .arg(
                     Arg::with_name("chromosome")
-                        .required(true)
-                        .help("the chromosome you want to visualize")
+                        .required_unless("regions")
+                        .help("the chromosome or region (e.g. chr1:1,000-2,000) you want to visualize")
                         .index(4),
                 )
                 .arg(
                     Arg::with_name("from")
-                        .required(true)
+                        .requires("to")
                         .help("the start of the region you want to visualize")
                         .index(5),
                 )
                 .arg(
                     Arg::with_name("to")
-                        .required(true)
+                        .requires("from")
                         .help("the end of the region you want to visualize")
                         .index(6),
+                )
+                .arg(
+                    Arg::with_name("regions")
+                        .long("regions")
+                        .takes_value(true)
+                        .conflicts_with("chromosome")
+                        .requires("output")
+                        .help("bed file with regions to visualize, one spec is written per region"),
+                )
+                .arg(
+                    Arg::with_name("output")
+                        .short("o")
+                        .long("output")
+                        .takes_value(true)
+                        .help("output directory for the specs of the --regions mode"),
+                )
+                .arg(
+                    Arg::with_name("format")
+                        .long("format")
+                        .takes_value(true)
+                        .possible_values(&["json", "compact-json", "vega-lite", "svg"])
+                        .default_value("json")
+                        .help("output a vega spec (json), a smaller vega spec storing every read only once (compact-json), a vega-lite spec or a rendered svg image"),
+                )
+                .arg(
+                    Arg::with_name("theme")
+                        .long("theme")
+                        .takes_value(true)
+                        .possible_values(&["default", "colorblind", "greyscale"])
+                        .default_value("default")
+                        .help("color palette for bases and variant types"),
+                )
+                .arg(
+                    Arg::with_name("height")
+                        .long("height")
+                        .takes_value(true)
+                        .help("height of the plot in pixels"),
+                )
+                .arg(
+                    Arg::with_name("row spacing")
+                        .long("row-spacing")
+                        .takes_value(true)
+                        .validator(|s| parse_row_spacing(&s).map(|_| ()))
+                        .help("space between rows as a fraction of the row height (0 to 1)"),
+                )
+                .arg(
+                    Arg::with_name("spec")
+                        .long("spec")
+                        .takes_value(true)
+                        .help("custom vega spec to use instead of the default one"),
+                )
+                .arg(
+                    Arg::with_name("hide soft clips")
+                        .long("hide-soft-clips")
+                        .help("don't show soft clipped bases as overhangs at the ends of the reads"),
+                )
+                .arg(
+                    Arg::with_name("color by strand")
+                        .long("color-by-strand")
+                        .help("colour matching bases by the strand of their read"),
+                )
+                .arg(
+                    Arg::with_name("min indel length")
+                        .long("min-indel-length")
+                        .takes_value(true)
+                        .help("hide insertions and deletions shorter than this, e.g. sequencing errors of long reads"),
+                )
+                .arg(
+                    Arg::with_name("alt only")
+                        .long("alt-only")
+                        .help("only show reads that support the alternative allele of a variant in the region"),
+                )
+                .arg(
+                    Arg::with_name("modification")
+                        .long("modification")
+                        .takes_value(true)
+                        .possible_values(&["5mC", "6mA"])
+                        .help("colour modified bases from the MM and ML tags by their probability and add a track with their mean per position"),
+                )
+                .arg(
+                    Arg::with_name("group by")
+                        .long("group-by")
+                        .takes_value(true)
+                        .possible_values(&["strand", "haplotype", "read-group", "sample", "mate-chromosome"])
+                        .help("show the reads in groups, e.g. per haplotype from the HP and PS tags"),
+                )
+                .arg(
+                    Arg::with_name("sort by base")
+                        .long("sort-by-base")
+                        .takes_value(true)
+                        .help("sort the reads by their base at this (1-based) position"),
+                )
+                .arg(
+                    Arg::with_name("insert size")
+                        .long("insert-size")
+                        .takes_value(true)
+                        .help("expected insert sizes like 200-600, estimated from the bam file if not given"),
                 ),
         )
         .subcommand(
@@ -220,6 +950,33 @@ fn main() -> Result<(), Box<dyn Error>> {
                         .short("r")
                         .required(false)
                         .help("write html to stdout"),
+                )
+                .arg(
+                    Arg::with_name("group by")
+                        .long("group-by")
+                        .takes_value(true)
+                        .possible_values(&["strand", "haplotype", "read-group", "sample", "mate-chromosome"])
+                        .help("show the reads in groups, e.g. per haplotype from the HP and PS tags"),
+                )
+                .arg(
+                    Arg::with_name("tls certs")
+                        .long("tls-certs")
+                        .takes_value(true)
+                        .requires("tls key")
+                        .help("PEM file with the certificate chain, to serve https instead of http"),
+                )
+                .arg(
+                    Arg::with_name("tls key")
+                        .long("tls-key")
+                        .takes_value(true)
+                        .requires("tls certs")
+                        .help("PEM file with the private key of the certificate"),
+                )
+                .arg(
+                    Arg::with_name("spec")
+                        .long("spec")
+                        .takes_value(true)
+                        .help("custom vega spec to use instead of the default one"),
                 ),
         )
         .get_matches();
@@ -228,12 +985,68 @@ fn main() -> Result<(), Box<dyn Error>> {
         Some("server") => {
             let params = matches.subcommand_matches("server").unwrap().clone();
 
-            rocket::ignite()
-                .manage(params)
-                .mount("/", StaticFiles::from("static"))
-                .mount("/api/v1", routes![reference, alignment, variant])
-                .attach(Compression::fairing())
-                .launch();
+            let cache_size = usize::from_str(params.value_of("cache size").unwrap())?;
+            let datasets = match params.value_of("catalogue") {
+                Some(catalogue) => Datasets::from_catalogue(Path::new(catalogue), cache_size)?,
+                None => Datasets::single(
+                    DatasetConfig {
+                        reference: PathBuf::from(params.value_of("fasta file").unwrap()),
+                        alignments: PathBuf::from(params.value_of("bam file").unwrap()),
+                        variants: PathBuf::from(params.value_of("vcf file").unwrap()),
+                        annotation: params.value_of("annotation").map(PathBuf::from),
+                        description: None,
+                        tracks: None,
+                        insert_size: match params.value_of("insert size") {
+                            Some(insert_size) => Some(InsertSizeRange::from_str(insert_size)?),
+                            None => None,
+                        },
+                    },
+                    cache_size,
+                )?,
+            };
+            let access = match params.value_of("access") {
+                Some(access) => Some(Access::from_file(Path::new(access))?),
+                None => None,
+            };
+            let level_of_detail = LevelOfDetail {
+                max_detail_span: u64::from_str(params.value_of("max detail span").unwrap())?,
+                bins: u64::from_str(params.value_of("overview bins").unwrap())?,
+            };
+
+            let config = Config::build(Environment::active()?)
+                .address(params.value_of("address").unwrap())
+                .port(u16::from_str(params.value_of("port").unwrap())?);
+            let config = with_tls(config, &params)?.finalize()?;
+            let prefix = normalize_prefix(params.value_of("url prefix").unwrap());
+            let base = if prefix.is_empty() { "/" } else { &prefix };
+
+            let server = rocket::custom(config)
+                .manage(datasets)
+                .manage(access)
+                .manage(level_of_detail)
+                .manage(StaticDir(params.value_of("static dir").map(PathBuf::from)))
+                .mount(base, routes![home, static_asset])
+                .mount(
+                    &format!("{}/api/v1", prefix),
+                    routes![
+                        datasets,
+                        reference,
+                        dataset_reference,
+                        alignment,
+                        dataset_alignment,
+                        variant,
+                        dataset_variant,
+                        region,
+                        dataset_region,
+                        search,
+                        dataset_search,
+                        read,
+                        dataset_read
+                    ],
+                )
+                .register(catchers![unauthorized]);
+
+            server.attach(Compression::fairing()).launch();
             Ok(())
         }
         Some("static") => {
@@ -242,47 +1055,114 @@ fn main() -> Result<(), Box<dyn Error>> {
             let fasta_path = Path::new(static_matches.value_of("fasta file").unwrap());
             let bam_path = Path::new(static_matches.value_of("bam file").unwrap());
             let vcf_path = Path::new(static_matches.value_of("vcf file").unwrap());
-            let chromosome = String::from(static_matches.value_of("chromosome").unwrap());
-            let from = u64::from_str(static_matches.value_of("from").unwrap()).unwrap();
-            let to = u64::from_str(static_matches.value_of("to").unwrap()).unwrap();
 
-            let data = create_data(&fasta_path, &vcf_path, &bam_path, chromosome, from, to);
-            let out = manipulate_json(data, from, to);
+            let format = static_matches.value_of("format").unwrap();
+            let specs = read_specs(static_matches.value_of("spec").map(Path::new))?;
+            let options = PlotOptions {
+                theme: Theme::from_str(static_matches.value_of("theme").unwrap())?,
+                height: match static_matches.value_of("height") {
+                    Some(height) => Some(u32::from_str(height)?),
+                    None => None,
+                },
+                row_spacing: match static_matches.value_of("row spacing") {
+                    Some(row_spacing) => Some(parse_row_spacing(row_spacing)?),
+                    None => None,
+                },
+                soft_clips: !static_matches.is_present("hide soft clips"),
+                min_indel_length: match static_matches.value_of("min indel length") {
+                    Some(min_length) => u32::from_str(min_length)?,
+                    None => 0,
+                },
+                color_by_strand: static_matches.is_present("color by strand"),
+                alt_only: static_matches.is_present("alt only"),
+                modification: match static_matches.value_of("modification") {
+                    Some(modification) => Some(ModificationType::from_str(modification)?),
+                    None => None,
+                },
+            };
+            options.validate()?;
+
+            let order = ReadOrder {
+                group_by: match static_matches.value_of("group by") {
+                    Some(group_by) => Some(GroupBy::from_str(group_by)?),
+                    None => None,
+                },
+                // The API uses 0-based positions
+                sort_by_base: match static_matches.value_of("sort by base") {
+                    Some(position) => Some(u64::from_str(position)?.saturating_sub(1)),
+                    None => None,
+                },
+            };
+
+            let insert_sizes = match static_matches.value_of("insert size") {
+                Some(insert_size) => Some(InsertSizeRange::from_str(insert_size)?),
+                None => InsertSizeRange::estimate(bam_path, INSERT_SIZE_SAMPLE)?,
+            };
+
+            let mut readers = Readers::open(&fasta_path, &bam_path, &vcf_path)?;
 
-            io::stdout().write(out.to_string().as_bytes())?;
+            if let Some(regions_path) = static_matches.value_of("regions") {
+                let out_dir = Path::new(static_matches.value_of("output").unwrap());
+                fs::create_dir_all(out_dir)?;
+
+                for region in read_bed(Path::new(regions_path))? {
+                    let out = render_static(
+                        &mut readers,
+                        &region,
+                        format,
+                        &specs,
+                        &options,
+                        &order,
+                        insert_sizes.as_ref(),
+                    )?;
+
+                    let out_path =
+                        out_dir.join(format!("{}.{}", region.file_name(), file_extension(format)));
+                    fs::write(out_path, out)?;
+                }
+            } else {
+                let chromosome = static_matches.value_of("chromosome").unwrap();
+                let region = match (
+                    static_matches.value_of("from"),
+                    static_matches.value_of("to"),
+                ) {
+                    (Some(from), Some(to)) => Region::new(
+                        String::from(chromosome),
+                        u64::from_str(from)?,
+                        u64::from_str(to)?,
+                    ),
+                    _ => Region::parse(chromosome)?,
+                };
+
+                let out = render_static(
+                    &mut readers,
+                    &region,
+                    format,
+                    &specs,
+                    &options,
+                    &order,
+                    insert_sizes.as_ref(),
+                )?;
+
+                io::stdout().write(out.as_bytes())?;
+            }
             Ok(())
         }
         Some("report") => {
             let params = matches.subcommand_matches("report").unwrap().clone();
 
             if params.is_present("render") {
-                let mut templates = Tera::default();
-                templates
-                    .add_raw_template(
-                        "report.html.tera",
-                        include_str!("../templates/report.html.tera"),
-                    )
-                    .unwrap();
-                let mut context = Context::new();
-                context.insert(
-                    "variants",
-                    &make_report(
-                        Path::new(params.value_of("vcf file").unwrap()),
-                        Path::new(params.value_of("fasta file").unwrap()),
-                        Path::new(params.value_of("bam file").unwrap()),
-                        params.value_of("chromosome").unwrap().parse().unwrap(),
-                    )?,
-                );
-
-                let html = templates.render("report.html.tera", &context).unwrap();
+                let html = render_report(&params)?;
 
                 stdout().write(html.as_bytes())?;
             } else {
-                rocket::ignite()
-                    .manage(params)
-                    .mount("/", routes![index])
-                    .attach(Template::fairing())
-                    .launch();
+                let server = if params.is_present("tls certs") {
+                    let config = with_tls(Config::build(Environment::active()?), &params)?;
+                    rocket::custom(config.finalize()?)
+                } else {
+                    rocket::ignite()
+                };
+                server.manage(params).mount("/", routes![index]).launch();
             }
             Ok(())
         }
@@ -0,0 +1,210 @@
+use alignment_reader::{AlignmentNucleobase, Marker};
+use std::collections::BTreeMap;
+use std::str::FromStr;
+
+/// A modified base call of a read, from its `MM` and `ML` tags.
+#[derive(Clone, Debug, PartialEq)]
+pub struct BaseModification {
+    // Index of the base in the sequence as stored in the record
+    pub(crate) read_index: usize,
+    // e.g. 5mC, 6mA or 5hmC
+    pub(crate) modification: String,
+    pub(crate) probability: f64,
+}
+
+/// The modifications reads can be coloured by.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ModificationType {
+    FiveMethylCytosine,
+    SixMethylAdenine,
+}
+
+impl ModificationType {
+    pub fn label(&self) -> &'static str {
+        match self {
+            ModificationType::FiveMethylCytosine => "5mC",
+            ModificationType::SixMethylAdenine => "6mA",
+        }
+    }
+}
+
+impl FromStr for ModificationType {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<ModificationType, String> {
+        match s {
+            "5mC" | "5mc" => Ok(ModificationType::FiveMethylCytosine),
+            "6mA" | "6ma" => Ok(ModificationType::SixMethylAdenine),
+            _ => Err(format!("unknown base modification '{}'", s)),
+        }
+    }
+}
+
+/// Modification calls of all reads at one position of the reference.
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct MethylationLevel {
+    pub(crate) start_position: f64,
+    pub(crate) end_position: f64,
+    pub(crate) modification: String,
+    pub(crate) calls: u32,
+    // Calls with a probability of at least 0.5
+    pub(crate) modified: u32,
+    pub(crate) probability: f64,
+}
+
+/// Decodes an `MM` tag like `C+m?,5,12,0;A+a,1;` with the probabilities of its `ML` tag.
+/// The skip counts refer to the bases of the read as it was sequenced, so reads on the reverse
+/// strand are counted from the end of their (reverse complemented) sequence.
+pub fn parse_modifications(
+    mm: &str,
+    ml: &[u8],
+    sequence: &[u8],
+    reverse: bool,
+) -> Vec<BaseModification> {
+    let mut modifications = Vec::new();
+    let mut ml_index = 0;
+
+    for entry in mm.split(';').filter(|e| !e.is_empty()) {
+        let mut fields = entry.split(',');
+        let header = fields.next().unwrap();
+        let skips: Vec<usize> = match fields.map(|f| f.parse()).collect() {
+            Ok(skips) => skips,
+            Err(_) => break,
+        };
+
+        let mut chars = header.chars();
+        let (base, strand) = match (chars.next(), chars.next()) {
+            (Some(base), Some(strand)) => (base.to_ascii_uppercase() as u8, strand),
+            _ => break,
+        };
+        let codes: String = chars.filter(|c| *c != '.' && *c != '?').collect();
+        // Codes are single letters, or a ChEBI number for a single modification
+        let codes: Vec<String> = if codes.chars().all(|c| c.is_ascii_digit()) {
+            vec![codes]
+        } else {
+            codes.chars().map(|c| c.to_string()).collect()
+        };
+
+        let positions = base_positions(base, sequence, reverse);
+        let mut position = 0;
+        for skip in skips {
+            position += skip;
+            let read_index = positions.get(position).cloned();
+            for code in codes.iter() {
+                let probability = match ml.get(ml_index) {
+                    Some(p) => (*p as f64 + 0.5) / 256.0,
+                    None => 1.0,
+                };
+                ml_index += 1;
+
+                // Modifications on the opposite strand are not shown
+                if let (Some(read_index), '+') = (read_index, strand) {
+                    modifications.push(BaseModification {
+                        read_index: read_index,
+                        modification: label(base, code),
+                        probability: probability,
+                    });
+                }
+            }
+            position += 1;
+        }
+    }
+
+    modifications
+}
+
+// Indices of a base in the stored sequence, in the order the read was sequenced
+fn base_positions(base: u8, sequence: &[u8], reverse: bool) -> Vec<usize> {
+    let wanted = if reverse { complement(base) } else { base };
+    let is_base = |b: &u8| wanted == b'N' || b.to_ascii_uppercase() == wanted;
+
+    if reverse {
+        (0..sequence.len())
+            .rev()
+            .filter(|i| is_base(&sequence[*i]))
+            .collect()
+    } else {
+        (0..sequence.len())
+            .filter(|i| is_base(&sequence[*i]))
+            .collect()
+    }
+}
+
+fn complement(base: u8) -> u8 {
+    match base {
+        b'A' => b'T',
+        b'C' => b'G',
+        b'G' => b'C',
+        b'T' | b'U' => b'A',
+        b => b,
+    }
+}
+
+fn label(base: u8, code: &str) -> String {
+    match (base, code) {
+        (b'C', "m") => String::from("5mC"),
+        (b'C', "h") => String::from("5hmC"),
+        (b'C', "f") => String::from("5fC"),
+        (b'C', "c") => String::from("5caC"),
+        (b'A', "a") => String::from("6mA"),
+        (b'T', "g") => String::from("5hmU"),
+        _ => format!("{}+{}", base as char, code),
+    }
+}
+
+/// Keeps the modified bases of the given type, all other modified bases are removed. Without a
+/// type, reads are not coloured by modification and all of them are removed.
+pub fn select_modifications(
+    bases: &mut Vec<AlignmentNucleobase>,
+    modification: Option<ModificationType>,
+) {
+    bases.retain(|b| {
+        b.marker_type != Marker::Modification
+            || modification.map_or(false, |m| b.bases == m.label())
+    });
+}
+
+/// Aggregates the modified bases of all reads, given as their start position and probability,
+/// by reference position.
+pub fn methylation_levels<I>(calls: I, modification: ModificationType) -> Vec<MethylationLevel>
+where
+    I: IntoIterator<Item = (f64, f64)>,
+{
+    let mut positions: BTreeMap<i64, (u32, u32, f64)> = BTreeMap::new();
+
+    for (start_position, probability) in calls {
+        let position = positions
+            .entry((start_position + 0.5) as i64)
+            .or_insert((0, 0, 0.0));
+        position.0 += 1;
+        if probability >= 0.5 {
+            position.1 += 1;
+        }
+        position.2 += probability;
+    }
+
+    positions
+        .into_iter()
+        .map(|(position, (calls, modified, probability))| MethylationLevel {
+            start_position: position as f64 - 0.5,
+            end_position: position as f64 + 0.5,
+            modification: modification.label().to_owned(),
+            calls: calls,
+            modified: modified,
+            probability: probability / calls as f64,
+        })
+        .collect()
+}
+
+/// Aggregates the modified bases of the given type, like `methylation_levels`.
+pub fn read_methylation_levels(
+    bases: &[AlignmentNucleobase],
+    modification: ModificationType,
+) -> Vec<MethylationLevel> {
+    let calls = bases
+        .iter()
+        .filter(|b| b.marker_type == Marker::Modification && b.bases == modification.label())
+        .map(|b| (b.start_position, b.probability.unwrap_or(1.0)));
+
+    methylation_levels(calls, modification)
+}
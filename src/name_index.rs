@@ -0,0 +1,177 @@
+use bio::io::{bed, gff};
+use region::Region;
+use rust_htslib::bcf::Read;
+use std::collections::HashMap;
+use std::error::Error;
+use std::path::Path;
+
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub enum LocusType {
+    Gene,
+    Variant,
+    Region,
+}
+
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct Locus {
+    pub(crate) name: String,
+    pub(crate) locus_type: LocusType,
+    pub(crate) region: Region,
+}
+
+/// Maps gene symbols and variant IDs (case-insensitive) to their loci. Built once per dataset.
+pub struct NameIndex {
+    loci: HashMap<String, Vec<Locus>>,
+}
+
+impl NameIndex {
+    pub fn build(
+        annotation_path: Option<&Path>,
+        vcf_path: &Path,
+    ) -> Result<NameIndex, Box<dyn Error>> {
+        let mut index = NameIndex {
+            loci: HashMap::new(),
+        };
+
+        if let Some(path) = annotation_path {
+            index.add_annotation(path)?;
+        }
+        index.add_variant_ids(vcf_path)?;
+
+        Ok(index)
+    }
+
+    /// Resolves a query, which is either a region string like `chr1:1,000-2,000`,
+    /// a gene symbol or a variant ID.
+    pub fn search(&self, query: &str) -> Vec<Locus> {
+        if let Ok(region) = Region::parse(query) {
+            return vec![Locus {
+                name: query.trim().to_owned(),
+                locus_type: LocusType::Region,
+                region: region,
+            }];
+        }
+
+        match self.loci.get(&query.trim().to_lowercase()) {
+            Some(loci) => loci.clone(),
+            None => Vec::new(),
+        }
+    }
+
+    fn add_annotation(&mut self, path: &Path) -> Result<(), Box<dyn Error>> {
+        let extension = path
+            .extension()
+            .and_then(|e| e.to_str())
+            .unwrap_or("")
+            .to_lowercase();
+
+        // Gene extents are the union of all features carrying the gene name,
+        // since GTF files often only contain exons and transcripts.
+        let mut genes: HashMap<(String, String), Region> = HashMap::new();
+
+        if extension == "bed" {
+            let mut reader = bed::Reader::from_file(path)?;
+            for r in reader.records() {
+                let rec = r?;
+                if let Some(name) = rec.name() {
+                    extend_gene(
+                        &mut genes,
+                        name.to_owned(),
+                        rec.chrom().to_owned(),
+                        rec.start(),
+                        rec.end(),
+                    );
+                }
+            }
+        } else {
+            let gff_type = match extension.as_ref() {
+                "gtf" | "gff2" => gff::GffType::GTF2,
+                _ => gff::GffType::GFF3,
+            };
+            let mut reader = gff::Reader::from_file(path, gff_type)?;
+            for r in reader.records() {
+                let rec = r?;
+                let attributes = rec.attributes();
+                let name = attributes
+                    .get("gene_name")
+                    .or_else(|| attributes.get("Name"))
+                    .or_else(|| attributes.get("gene_id"));
+
+                if let Some(name) = name {
+                    // gff positions are 1-based and inclusive
+                    extend_gene(
+                        &mut genes,
+                        name.to_owned(),
+                        rec.seqname().to_owned(),
+                        *rec.start() - 1,
+                        *rec.end(),
+                    );
+                }
+            }
+        }
+
+        for ((name, _), region) in genes {
+            self.insert(Locus {
+                name: name,
+                locus_type: LocusType::Gene,
+                region: region,
+            });
+        }
+
+        Ok(())
+    }
+
+    fn add_variant_ids(&mut self, path: &Path) -> Result<(), Box<dyn Error>> {
+        let mut vcf = rust_htslib::bcf::Reader::from_path(&path)?;
+        let header = vcf.header().clone();
+
+        for r in vcf.records() {
+            let rec = r?;
+            let id = String::from_utf8(rec.id())?;
+
+            if id == "." {
+                continue;
+            }
+
+            let chrom = String::from_utf8(header.rid2name(rec.rid().unwrap())?.to_owned())?;
+            let pos = rec.pos() as u64;
+            let len = rec.alleles()[0].len() as u64;
+
+            for i in id.split(';') {
+                self.insert(Locus {
+                    name: i.to_owned(),
+                    locus_type: LocusType::Variant,
+                    region: Region::new(chrom.clone(), pos, pos + len),
+                });
+            }
+        }
+
+        Ok(())
+    }
+
+    fn insert(&mut self, locus: Locus) {
+        self.loci
+            .entry(locus.name.to_lowercase())
+            .or_insert_with(Vec::new)
+            .push(locus);
+    }
+}
+
+fn extend_gene(
+    genes: &mut HashMap<(String, String), Region>,
+    name: String,
+    chrom: String,
+    from: u64,
+    to: u64,
+) {
+    let region = genes
+        .entry((name, chrom.clone()))
+        .or_insert_with(|| Region::new(chrom, from, to));
+
+    if from < region.from {
+        region.from = from;
+    }
+    if to > region.to {
+        region.to = to;
+    }
+}
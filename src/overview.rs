@@ -0,0 +1,150 @@
+use region::UnknownChromosome;
+use rust_htslib::bam::record::Cigar;
+use rust_htslib::{bam, bam::Read, bcf, bcf::Read as BcfRead};
+use std::error::Error;
+
+// Unmapped, secondary, qc failed and duplicate reads, which are also left out of the coverage track
+const SKIPPED_FLAGS: u16 = 0x4 | 0x100 | 0x200 | 0x400;
+
+// Bases at the start of each overview bin whose reads are fetched
+const OVERVIEW_SAMPLE_SPAN: u64 = 1000;
+
+/// Summary of a part of a region that is too large to show single reads.
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct Bin {
+    pub(crate) start_position: f64,
+    pub(crate) end_position: f64,
+    pub(crate) coverage: f64,
+    pub(crate) reads: u32,
+    pub(crate) variants: u32,
+}
+
+/// Regions that span more than `max_detail_span` bases are shown as an overview with the
+/// given number of bins.
+#[derive(Serialize, Clone, Copy, Debug, PartialEq)]
+pub struct LevelOfDetail {
+    pub(crate) max_detail_span: u64,
+    pub(crate) bins: u64,
+}
+
+impl LevelOfDetail {
+    pub fn is_overview(&self, from: u64, to: u64) -> bool {
+        to.saturating_sub(from) > self.max_detail_span
+    }
+
+    /// Fails for regions that are too large to return single reads for.
+    pub fn check_detail_span(&self, from: u64, to: u64) -> Result<(), String> {
+        if self.is_overview(from, to) {
+            Err(format!(
+                "region of {} bp is larger than {} bp, request an overview from the region endpoint",
+                to.saturating_sub(from),
+                self.max_detail_span
+            ))
+        } else {
+            Ok(())
+        }
+    }
+}
+
+impl Default for LevelOfDetail {
+    fn default() -> LevelOfDetail {
+        LevelOfDetail {
+            max_detail_span: 10000,
+            bins: 500,
+        }
+    }
+}
+
+/// Mean coverage, number of starting reads and number of variants in evenly sized bins. Reads are
+/// only fetched for the first `OVERVIEW_SAMPLE_SPAN` bases of each bin, so that large regions don't
+/// take longer than about `bins` small ones; the numbers of larger bins are estimated from them.
+/// Variants are counted in the whole bin.
+pub fn fetch_overview(
+    bam: &mut bam::IndexedReader,
+    vcf: &mut bcf::IndexedReader,
+    chrom: String,
+    from: u64,
+    to: u64,
+    bins: u64,
+) -> Result<Vec<Bin>, Box<dyn Error>> {
+    let bins = bins.max(1);
+    let bin_size = ((to - from + bins - 1) / bins).max(1);
+    let bin_count = ((to - from + bin_size - 1) / bin_size) as usize;
+
+    let tid = match bam.header().tid(chrom.as_bytes()) {
+        Some(tid) => tid,
+        None => return Err(UnknownChromosome(chrom).into()),
+    };
+    let rid = match vcf.header().name2rid(chrom.as_bytes()) {
+        Ok(rid) => rid,
+        Err(_) => return Err(UnknownChromosome(chrom).into()),
+    };
+
+    let mut overview = Vec::with_capacity(bin_count);
+    for i in 0..bin_count {
+        let bin_start = from + i as u64 * bin_size;
+        let bin_end = (bin_start + bin_size).min(to);
+        let sample_end = (bin_start + OVERVIEW_SAMPLE_SPAN).min(bin_end);
+        let (aligned_bases, reads) = sample_reads(bam, tid, bin_start, sample_end)?;
+        let scale = (bin_end - bin_start) as f64 / (sample_end - bin_start) as f64;
+
+        overview.push(Bin {
+            start_position: bin_start as f64 - 0.5,
+            end_position: bin_end as f64 - 0.5,
+            coverage: aligned_bases as f64 / (sample_end - bin_start) as f64,
+            reads: (reads as f64 * scale).round() as u32,
+            variants: 0,
+        });
+    }
+
+    vcf.fetch(rid, from, to)?;
+    for r in vcf.records() {
+        let pos = r?.pos() as u64;
+
+        if pos >= from && pos < to {
+            overview[((pos - from) / bin_size) as usize].variants += 1;
+        }
+    }
+
+    Ok(overview)
+}
+
+/// Number of bases aligned within `[from, to)` and number of reads starting there.
+fn sample_reads(
+    bam: &mut bam::IndexedReader,
+    tid: u32,
+    from: u64,
+    to: u64,
+) -> Result<(u64, u32), Box<dyn Error>> {
+    let mut aligned_bases = 0;
+    let mut reads = 0;
+
+    bam.fetch(tid, from, to)?;
+    for r in bam.records() {
+        let rec = r?;
+
+        if rec.flags() & SKIPPED_FLAGS != 0 {
+            continue;
+        }
+
+        let start = rec.pos() as u64;
+        if start >= from && start < to {
+            reads += 1;
+        }
+
+        let mut pos = start;
+        for c in rec.cigar().iter() {
+            match c {
+                Cigar::Match(len) | Cigar::Equal(len) | Cigar::Diff(len) => {
+                    let block_end = (pos + *len as u64).min(to);
+                    aligned_bases += block_end.saturating_sub(pos.max(from));
+                    pos += *len as u64;
+                }
+                Cigar::Del(len) | Cigar::RefSkip(len) => pos += *len as u64,
+                _ => {}
+            }
+        }
+    }
+
+    Ok((aligned_bases, reads))
+}
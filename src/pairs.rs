@@ -0,0 +1,178 @@
+use rust_htslib::{bam, bam::Read};
+use std::error::Error;
+use std::path::Path;
+use std::str::FromStr;
+
+/// Number of pairs that are read from the start of a bam file to estimate the insert sizes.
+pub const INSERT_SIZE_SAMPLE: usize = 10000;
+
+// At most this many records per sampled pair are examined, so that files with few or no proper
+// pairs, like long read data, are not read to the end
+const RECORDS_PER_SAMPLED_PAIR: usize = 10;
+
+/// How the two reads of a pair are placed relative to each other, like IGV's colouring by
+/// insert size and pair orientation. Forward-reverse pairs with an insert size in the
+/// expected range are `Normal`.
+#[derive(Serialize, Clone, Copy, Debug, PartialEq)]
+pub enum PairClass {
+    Normal,
+    Unpaired,
+    MateUnmapped,
+    InterChromosomal,
+    LargeInsert,
+    SmallInsert,
+    ReverseForward,
+    ForwardForward,
+    ReverseReverse,
+}
+
+impl PairClass {
+    /// Whether reads of the class get their own colour instead of the colour of a match.
+    pub fn has_color(self) -> bool {
+        match self {
+            PairClass::Normal | PairClass::Unpaired | PairClass::MateUnmapped => false,
+            _ => true,
+        }
+    }
+}
+
+impl Default for PairClass {
+    fn default() -> PairClass {
+        PairClass::Normal
+    }
+}
+
+/// Insert sizes that are expected for a library. Pairs outside of the range are marked as
+/// `LargeInsert` or `SmallInsert`.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub struct InsertSizeRange {
+    pub(crate) min: u64,
+    pub(crate) max: u64,
+}
+
+impl InsertSizeRange {
+    /// Takes the 0.5th and 99.5th percentile of the given insert sizes as expected range.
+    pub fn from_sizes(mut sizes: Vec<u64>) -> Option<InsertSizeRange> {
+        if sizes.is_empty() {
+            return None;
+        }
+        sizes.sort();
+
+        let percentile = |p: f64| sizes[(p * (sizes.len() - 1) as f64).round() as usize];
+        Some(InsertSizeRange {
+            min: percentile(0.005),
+            max: percentile(0.995),
+        })
+    }
+
+    /// Estimates the range from the first pairs of a bam file that are properly aligned in
+    /// forward-reverse orientation, looking at no more than 10 records per pair of the sample.
+    /// Returns `None` if there are no such pairs among them.
+    pub fn estimate(path: &Path, sample: usize) -> Result<Option<InsertSizeRange>, Box<dyn Error>> {
+        let mut bam = bam::Reader::from_path(path)?;
+        let mut sizes = Vec::new();
+
+        for r in bam
+            .records()
+            .take(sample.saturating_mul(RECORDS_PER_SAMPLED_PAIR))
+        {
+            let rec = r?;
+            // Each pair is only counted once, from the read with the positive insert size
+            if rec.is_paired()
+                && rec.is_proper_pair()
+                && !rec.is_unmapped()
+                && !rec.is_mate_unmapped()
+                && !rec.is_secondary()
+                && !rec.is_supplementary()
+                && !rec.is_duplicate()
+                && rec.tid() == rec.mtid()
+                && rec.insert_size() > 0
+                && orientation(&rec) == PairClass::Normal
+            {
+                sizes.push(rec.insert_size() as u64);
+                if sizes.len() >= sample {
+                    break;
+                }
+            }
+        }
+
+        Ok(InsertSizeRange::from_sizes(sizes))
+    }
+
+    pub fn classify(&self, class: PairClass, insert_size: i64) -> PairClass {
+        if class != PairClass::Normal {
+            return class;
+        }
+
+        let insert_size = insert_size.abs() as u64;
+        if insert_size > self.max {
+            PairClass::LargeInsert
+        } else if insert_size < self.min {
+            PairClass::SmallInsert
+        } else {
+            PairClass::Normal
+        }
+    }
+}
+
+/// Parses a range like `200-600`.
+impl FromStr for InsertSizeRange {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<InsertSizeRange, String> {
+        let error = || format!("invalid insert size range '{}', expected e.g. 200-600", s);
+        let mut bounds = s.splitn(2, '-');
+        let min = bounds
+            .next()
+            .and_then(|min| min.trim().parse().ok())
+            .ok_or_else(error)?;
+        let max = bounds
+            .next()
+            .and_then(|max| max.trim().parse().ok())
+            .ok_or_else(error)?;
+
+        if min > max {
+            return Err(error());
+        }
+
+        Ok(InsertSizeRange { min: min, max: max })
+    }
+}
+
+/// Classifies a read by the placement of its mate, without looking at the insert size.
+pub fn classify_pair(record: &bam::Record) -> PairClass {
+    if !record.is_paired() {
+        PairClass::Unpaired
+    } else if record.is_unmapped() || record.is_mate_unmapped() {
+        PairClass::MateUnmapped
+    } else if record.tid() != record.mtid() {
+        PairClass::InterChromosomal
+    } else {
+        orientation(record)
+    }
+}
+
+fn orientation(record: &bam::Record) -> PairClass {
+    let reverse = record.is_reverse();
+    let mate_reverse = record.is_mate_reverse();
+
+    if reverse == mate_reverse {
+        return if reverse {
+            PairClass::ReverseReverse
+        } else {
+            PairClass::ForwardForward
+        };
+    }
+
+    // Strand of the leftmost read of the pair
+    let left_reverse = if record.pos() <= record.mpos() {
+        reverse
+    } else {
+        mate_reverse
+    };
+    if left_reverse {
+        PairClass::ReverseForward
+    } else {
+        PairClass::Normal
+    }
+}
@@ -0,0 +1,160 @@
+use super::*;
+use alignment_reader::{classify_insert_sizes, fetch_alignments, make_nucleobases, Marker};
+use fasta_reader::read_fasta;
+use pairs::{InsertSizeRange, PairClass};
+use rust_htslib::bam;
+use serde_json::Value;
+use std::collections::BTreeMap;
+
+fn pair_classes(insert_sizes: Option<&InsertSizeRange>) -> BTreeMap<String, PairClass> {
+    let mut bam =
+        bam::IndexedReader::from_path(Path::new("tests/resources/orientation.bam")).unwrap();
+    let mut alignments = fetch_alignments(&mut bam, String::from("chr1"), 0, 123).unwrap();
+    if let Some(insert_sizes) = insert_sizes {
+        classify_insert_sizes(&mut alignments, insert_sizes);
+    }
+    let ref_bases = read_fasta(
+        Path::new("tests/resources/ref.fa"),
+        String::from("chr1"),
+        0,
+        123,
+    )
+    .unwrap();
+    let (_bases, matches) = make_nucleobases(&ref_bases, alignments, 0, 123);
+
+    matches
+        .into_iter()
+        .filter(|m| m.marker_type == Marker::Match)
+        .map(|m| (m.name, m.pair_class))
+        .collect()
+}
+
+// Renders the pairs of orientation.bam, with pairs outside of 50-100 bp marked
+fn render_pairs(format: &str) -> String {
+    let mut readers = Readers::open(
+        Path::new("tests/resources/ref.fa"),
+        Path::new("tests/resources/orientation.bam"),
+        Path::new("tests/resources/report-test.vcf.gz"),
+    )
+    .unwrap();
+
+    render_static(
+        &mut readers,
+        &Region::new(String::from("chr1"), 0, 123),
+        format,
+        &read_specs(None).unwrap(),
+        &PlotOptions::default(),
+        &ReadOrder::default(),
+        Some(&InsertSizeRange::from_str("50-100").unwrap()),
+    )
+    .unwrap()
+}
+
+#[test]
+fn orientation_test() {
+    let classes = pair_classes(None);
+
+    assert_eq!(classes["normal"], PairClass::Normal);
+    assert_eq!(classes["large"], PairClass::Normal);
+    assert_eq!(classes["reverse_forward"], PairClass::ReverseForward);
+    assert_eq!(classes["forward_forward"], PairClass::ForwardForward);
+    assert_eq!(classes["reverse_reverse"], PairClass::ReverseReverse);
+    assert_eq!(classes["inter_chromosomal"], PairClass::InterChromosomal);
+    assert_eq!(classes["mate_unmapped"], PairClass::MateUnmapped);
+    assert_eq!(classes["unpaired"], PairClass::Unpaired);
+}
+
+#[test]
+fn insert_size_test() {
+    let insert_sizes = InsertSizeRange::from_str("50-100").unwrap();
+    let classes = pair_classes(Some(&insert_sizes));
+
+    assert_eq!(classes["normal"], PairClass::Normal);
+    assert_eq!(classes["large"], PairClass::LargeInsert);
+    assert_eq!(classes["small"], PairClass::SmallInsert);
+    // Pairs in the wrong orientation keep their class
+    assert_eq!(classes["reverse_forward"], PairClass::ReverseForward);
+
+    assert!(InsertSizeRange::from_str("100-50").is_err());
+    assert!(InsertSizeRange::from_str("100").is_err());
+}
+
+#[test]
+fn estimate_insert_sizes_test() {
+    let estimated = InsertSizeRange::estimate(Path::new("tests/resources/pairs.bam"), 100)
+        .unwrap()
+        .unwrap();
+    assert_eq!(estimated, InsertSizeRange { min: 65, max: 90 });
+
+    // Only pairs in forward-reverse orientation are counted
+    let estimated = InsertSizeRange::estimate(Path::new("tests/resources/orientation.bam"), 100)
+        .unwrap()
+        .unwrap();
+    assert_eq!(estimated, InsertSizeRange { min: 25, max: 116 });
+
+    // Files without pairs have no expected insert sizes
+    let estimated =
+        InsertSizeRange::estimate(Path::new("tests/resources/haplotypes.bam"), 100).unwrap();
+    assert_eq!(estimated, None);
+
+    let sizes = (1..=1000).collect();
+    assert_eq!(
+        InsertSizeRange::from_sizes(sizes),
+        Some(InsertSizeRange { min: 6, max: 995 })
+    );
+    assert_eq!(InsertSizeRange::from_sizes(Vec::new()), None);
+}
+
+#[test]
+fn static_pair_class_test() {
+    let specs: Value = serde_json::from_str(&render_pairs("json")).unwrap();
+    let values = specs["data"][1]["values"].as_array().unwrap();
+    let pair_class = |name: &str| {
+        values
+            .iter()
+            .find(|v| v["name"] == name && v["marker_type"] == "Match")
+            .map(|v| v["pair_class"].clone())
+    };
+    assert_eq!(pair_class("large"), Some(json!("LargeInsert")));
+    assert_eq!(pair_class("small"), Some(json!("SmallInsert")));
+    assert_eq!(pair_class("forward_forward"), Some(json!("ForwardForward")));
+    assert_eq!(pair_class("normal"), Some(json!("Normal")));
+}
+
+#[test]
+fn compact_pair_class_test() {
+    let specs: Value = serde_json::from_str(&render_pairs("compact-json")).unwrap();
+    let reads = specs["data"][1]["values"].as_array().unwrap();
+    let large = reads.iter().find(|r| r["name"] == "large").unwrap();
+    assert_eq!(large["pair_class"], "LargeInsert");
+
+    let lookup = specs["data"][2]["transform"][0]["values"]
+        .as_array()
+        .unwrap();
+    assert!(lookup.contains(&json!("pair_class")));
+}
+
+#[test]
+fn vega_lite_pair_class_test() {
+    let specs: Value = serde_json::from_str(&render_pairs("vega-lite")).unwrap();
+    let values = specs["data"]["values"].as_array().unwrap();
+    assert!(values.iter().any(|v| v["pair_class"] == "ReverseReverse"));
+
+    let calculate = specs["transform"][1]["calculate"].as_str().unwrap();
+    assert!(calculate.contains("datum.pair_class"));
+}
+
+#[test]
+fn svg_pair_class_test() {
+    let svg = render_pairs("svg");
+    for pair_class in &[
+        "InterChromosomal",
+        "LargeInsert",
+        "SmallInsert",
+        "ReverseForward",
+    ] {
+        let color = Theme::Default.color(pair_class).unwrap();
+        assert!(svg.contains(&format!("stroke=\"{}\"", color)));
+        assert!(svg.contains(&format!(">{}</text>", pair_class)));
+    }
+}
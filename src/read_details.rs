@@ -0,0 +1,157 @@
+use alignment_reader::decode_flags;
+use aux_tags::aux_fields;
+use region::{Region, UnknownChromosome};
+use rust_htslib::{bam, bam::Read};
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::error::Error;
+
+/// Another alignment of the same read, taken from its `SA` tag.
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct SupplementaryAlignment {
+    pub(crate) region: Region,
+    pub(crate) reverse: bool,
+    pub(crate) cigar: String,
+    pub(crate) mapq: u8,
+    pub(crate) edit_distance: u32,
+}
+
+/// Everything that is known about one record of a read.
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct ReadDetails {
+    pub(crate) name: String,
+    pub(crate) flags: Vec<u16>,
+    pub(crate) region: Region,
+    pub(crate) cigar: String,
+    pub(crate) mapq: u8,
+    pub(crate) insert_size: i64,
+    // Where the mate is aligned, so the viewer can jump to it
+    pub(crate) mate: Option<Region>,
+    pub(crate) supplementary: Vec<SupplementaryAlignment>,
+    pub(crate) sequence: String,
+    pub(crate) qualities: Vec<u8>,
+    pub(crate) tags: BTreeMap<String, Value>,
+}
+
+/// Returns all records with the given name that overlap a region, usually the read itself and
+/// its mate or supplementary alignments. Fails if the chromosome is not in the bam file.
+pub fn fetch_read_details(
+    bam: &mut bam::IndexedReader,
+    chrom: String,
+    from: u64,
+    to: u64,
+    name: &str,
+) -> Result<Vec<ReadDetails>, Box<dyn Error>> {
+    let tid = match bam.header().tid(chrom.as_bytes()) {
+        Some(tid) => tid,
+        None => return Err(UnknownChromosome(chrom).into()),
+    };
+    bam.fetch(tid, from, to)?;
+
+    let mut details = Vec::new();
+    let mut records = Vec::new();
+    for r in bam.records() {
+        let rec = r?;
+        if rec.qname() == name.as_bytes() {
+            records.push(rec);
+        }
+    }
+
+    for rec in records {
+        let tags = aux_fields(&rec);
+
+        let mate = if rec.is_paired() && !rec.is_mate_unmapped() && rec.mtid() >= 0 {
+            let mate_chrom = String::from_utf8_lossy(bam.header().tid2name(rec.mtid() as u32));
+            let mate_length = match tags.get("MC") {
+                Some(Value::String(cigar)) => reference_length(cigar).unwrap_or(1),
+                _ => 1,
+            };
+            Some(Region::new(
+                mate_chrom.into_owned(),
+                rec.mpos() as u64,
+                rec.mpos() as u64 + mate_length.max(1),
+            ))
+        } else {
+            None
+        };
+
+        let supplementary = match tags.get("SA") {
+            Some(Value::String(sa)) => parse_supplementary(sa),
+            _ => Vec::new(),
+        };
+
+        details.push(ReadDetails {
+            name: name.to_owned(),
+            flags: decode_flags(rec.flags()),
+            region: Region::new(
+                chrom.clone(),
+                rec.pos() as u64,
+                rec.cigar().end_pos() as u64,
+            ),
+            cigar: rec.cigar().to_string(),
+            mapq: rec.mapq(),
+            insert_size: rec.insert_size(),
+            mate: mate,
+            supplementary: supplementary,
+            sequence: String::from_utf8(rec.seq().as_bytes()).unwrap(),
+            qualities: rec.qual().to_vec(),
+            tags: tags,
+        });
+    }
+
+    Ok(details)
+}
+
+/// Number of reference bases that a CIGAR string like `10S40M2D50M` covers.
+pub fn reference_length(cigar: &str) -> Option<u64> {
+    let mut length = 0;
+    let mut number = String::new();
+
+    for c in cigar.chars() {
+        if c.is_ascii_digit() {
+            number.push(c);
+            continue;
+        }
+
+        let count: u64 = number.parse().ok()?;
+        number.clear();
+        match c {
+            'M' | 'D' | 'N' | '=' | 'X' => length += count,
+            'I' | 'S' | 'H' | 'P' => {}
+            _ => return None,
+        }
+    }
+
+    if number.is_empty() {
+        Some(length)
+    } else {
+        None
+    }
+}
+
+/// Parses an `SA` tag, which lists alignments as `chrom,pos,strand,CIGAR,mapQ,NM;`
+/// with 1-based positions.
+pub fn parse_supplementary(sa: &str) -> Vec<SupplementaryAlignment> {
+    sa.split(';')
+        .filter_map(|alignment| {
+            let fields: Vec<&str> = alignment.split(',').collect();
+            if fields.len() != 6 {
+                return None;
+            }
+
+            let pos: u64 = fields[1].parse().ok()?;
+            let start = pos.checked_sub(1)?;
+            Some(SupplementaryAlignment {
+                region: Region::new(
+                    fields[0].to_owned(),
+                    start,
+                    start + reference_length(fields[3])?.max(1),
+                ),
+                reverse: fields[2] == "-",
+                cigar: fields[3].to_owned(),
+                mapq: fields[4].parse().ok()?,
+                edit_distance: fields[5].parse().ok()?,
+            })
+        })
+        .collect()
+}
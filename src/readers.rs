@@ -0,0 +1,108 @@
+use bio::io::fasta;
+use rust_htslib::{bam, bcf};
+use std::error::Error;
+use std::fs::File;
+use std::ops::{Deref, DerefMut};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+/// Open readers for one fasta, bam and vcf file, so that several regions can be
+/// fetched without reopening the files and reloading their indices.
+pub struct Readers {
+    pub(crate) fasta: fasta::IndexedReader<File>,
+    pub(crate) bam: bam::IndexedReader,
+    pub(crate) vcf: bcf::IndexedReader,
+}
+
+impl Readers {
+    pub fn open(
+        fasta_path: &Path,
+        bam_path: &Path,
+        vcf_path: &Path,
+    ) -> Result<Readers, Box<dyn Error>> {
+        Ok(Readers {
+            fasta: fasta::IndexedReader::from_file(&fasta_path)?,
+            bam: bam::IndexedReader::from_path(&bam_path)?,
+            vcf: bcf::IndexedReader::from_path(&vcf_path)?,
+        })
+    }
+}
+
+// Number of idle readers a pool keeps open, readers returned beyond that are closed
+const MAX_IDLE_READERS: usize = 16;
+
+/// A pool of open readers shared by the server workers. Each request takes idle readers
+/// from the pool (or opens new ones) and puts them back afterwards.
+pub struct ReaderPool {
+    pub(crate) fasta_path: PathBuf,
+    pub(crate) bam_path: PathBuf,
+    pub(crate) vcf_path: PathBuf,
+    idle: Mutex<Vec<Readers>>,
+    max_idle: usize,
+}
+
+impl ReaderPool {
+    pub fn new(fasta_path: &Path, bam_path: &Path, vcf_path: &Path) -> ReaderPool {
+        ReaderPool {
+            fasta_path: fasta_path.to_owned(),
+            bam_path: bam_path.to_owned(),
+            vcf_path: vcf_path.to_owned(),
+            idle: Mutex::new(Vec::new()),
+            max_idle: MAX_IDLE_READERS,
+        }
+    }
+
+    /// Keeps at most `max_idle` readers open between requests.
+    pub fn with_max_idle(mut self, max_idle: usize) -> ReaderPool {
+        self.max_idle = max_idle;
+        self
+    }
+
+    /// Number of open readers that are waiting for a request.
+    pub fn idle_count(&self) -> usize {
+        self.idle.lock().unwrap().len()
+    }
+
+    pub fn get(&self) -> Result<PooledReaders, Box<dyn Error>> {
+        let idle = self.idle.lock().unwrap().pop();
+        let readers = match idle {
+            Some(readers) => readers,
+            None => Readers::open(&self.fasta_path, &self.bam_path, &self.vcf_path)?,
+        };
+
+        Ok(PooledReaders {
+            pool: self,
+            readers: Some(readers),
+        })
+    }
+}
+
+pub struct PooledReaders<'a> {
+    pool: &'a ReaderPool,
+    readers: Option<Readers>,
+}
+
+impl<'a> Deref for PooledReaders<'a> {
+    type Target = Readers;
+
+    fn deref(&self) -> &Readers {
+        self.readers.as_ref().unwrap()
+    }
+}
+
+impl<'a> DerefMut for PooledReaders<'a> {
+    fn deref_mut(&mut self) -> &mut Readers {
+        self.readers.as_mut().unwrap()
+    }
+}
+
+impl<'a> Drop for PooledReaders<'a> {
+    fn drop(&mut self) {
+        if let Some(readers) = self.readers.take() {
+            let mut idle = self.pool.idle.lock().unwrap();
+            if idle.len() < self.pool.max_idle {
+                idle.push(readers);
+            }
+        }
+    }
+}
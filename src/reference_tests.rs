@@ -1,5 +1,5 @@
 use super::*;
-use fasta_reader::get_fasta_length;
+use fasta_reader::{get_fasta_length, read_fasta};
 use std::path::Path;
 
 #[test]
@@ -9,7 +9,8 @@ fn reference_test() {
         String::from("chr1"),
         1,
         10,
-    );
+    )
+    .unwrap();
 
     let mut compare_ref = Vec::new();
 
@@ -21,6 +22,7 @@ fn reference_test() {
             end_position: pos + 0.5,
             marker_type: char,
             row: 0,
+            soft_masked: false,
         };
         compare_ref.push(base);
         pos += 1.0;
@@ -35,7 +37,8 @@ fn empty_reference_test() {
         String::from("chr1"),
         11,
         11,
-    );
+    )
+    .unwrap();
 
     let compare_ref: Vec<Nucleobase> = Vec::new();
 
@@ -0,0 +1,103 @@
+use bio::io::bed;
+use regex::Regex;
+use std::error::Error;
+use std::fmt;
+use std::path::Path;
+
+lazy_static! {
+    static ref REGION: Regex = Regex::new(r"^\s*([^\s:]+):([\d,]+)(?:-([\d,]+))?\s*$").unwrap();
+}
+
+/// A genomic region with a 0-based, half-open interval `[from, to)`, matching the coordinates
+/// that are passed to the fasta, bam and vcf readers.
+#[derive(Serialize, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Region {
+    pub(crate) chromosome: String,
+    pub(crate) from: u64,
+    pub(crate) to: u64,
+}
+
+impl Region {
+    pub fn new(chromosome: String, from: u64, to: u64) -> Region {
+        Region {
+            chromosome: chromosome,
+            from: from,
+            to: to,
+        }
+    }
+
+    /// Parses a samtools-style region string like `chr1:1,000-2,000` (1-based, inclusive).
+    /// A single position (`chr1:1,500`) is turned into a region covering only that base.
+    pub fn parse(region: &str) -> Result<Region, Box<dyn Error>> {
+        let caps = match REGION.captures(region) {
+            Some(caps) => caps,
+            None => return Err(format!("invalid region string '{}'", region).into()),
+        };
+
+        let chromosome = caps[1].to_owned();
+        let start = parse_position(&caps[2])?;
+        let end = match caps.get(3) {
+            Some(end) => parse_position(end.as_str())?,
+            None => start,
+        };
+
+        if start == 0 || end < start {
+            return Err(format!("invalid region string '{}'", region).into());
+        }
+
+        Ok(Region::new(chromosome, start - 1, end))
+    }
+
+    /// A name that can be used as a file name, e.g. `chr1_1000-2000`.
+    pub fn file_name(&self) -> String {
+        let chrom: String = self
+            .chromosome
+            .chars()
+            .map(|c| {
+                if c.is_alphanumeric() || c == '.' {
+                    c
+                } else {
+                    '_'
+                }
+            })
+            .collect();
+        format!("{}_{}-{}", chrom, self.from + 1, self.to)
+    }
+}
+
+/// Reads all regions of a bed file. Bed intervals are already 0-based and half-open.
+pub fn read_bed(path: &Path) -> Result<Vec<Region>, Box<dyn Error>> {
+    let mut reader = bed::Reader::from_file(path)?;
+    let mut regions = Vec::new();
+
+    for r in reader.records() {
+        let rec = r?;
+        regions.push(Region::new(rec.chrom().to_owned(), rec.start(), rec.end()));
+    }
+
+    Ok(regions)
+}
+
+impl fmt::Display for Region {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}-{}", self.chromosome, self.from + 1, self.to)
+    }
+}
+
+/// A chromosome that a fasta, bam or vcf file does not contain. The server answers requests for
+/// it with 404.
+#[derive(Debug)]
+pub struct UnknownChromosome(pub String);
+
+impl fmt::Display for UnknownChromosome {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "unknown chromosome '{}'", self.0)
+    }
+}
+
+impl Error for UnknownChromosome {}
+
+fn parse_position(position: &str) -> Result<u64, Box<dyn Error>> {
+    let digits: String = position.chars().filter(|c| *c != ',').collect();
+    Ok(digits.parse()?)
+}
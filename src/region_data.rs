@@ -0,0 +1,223 @@
+use alignment_reader::{
+    hide_small_indels, hide_soft_clips, keep_alt_reads, AlignmentMatch, AlignmentNucleobase,
+    Coverage,
+};
+use cache::Caches;
+use columnar::ColumnarAlignments;
+use fasta_reader::Nucleobase;
+use grouping::{GroupSeparator, ReadOrder};
+use layout::Layouts;
+use modifications::{
+    read_methylation_levels, select_modifications, MethylationLevel, ModificationType,
+};
+use overview::{Bin, LevelOfDetail};
+use readers::ReaderPool;
+use region::Region;
+use std::error::Error;
+use std::str::FromStr;
+use variant_reader::Variant;
+
+/// The tracks that are included in a region response.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Tracks {
+    pub(crate) reference: bool,
+    pub(crate) variants: bool,
+    pub(crate) alignments: bool,
+    pub(crate) coverage: bool,
+}
+
+impl Default for Tracks {
+    fn default() -> Tracks {
+        Tracks {
+            reference: true,
+            variants: true,
+            alignments: true,
+            coverage: true,
+        }
+    }
+}
+
+impl Tracks {
+    /// Whether an overview of the reads and variants is included for large regions.
+    pub fn has_overview(&self) -> bool {
+        self.variants || self.alignments || self.coverage
+    }
+}
+
+impl FromStr for Tracks {
+    type Err = String;
+
+    /// Parses a comma separated list like `reference,alignments`.
+    fn from_str(s: &str) -> Result<Tracks, String> {
+        let mut tracks = Tracks {
+            reference: false,
+            variants: false,
+            alignments: false,
+            coverage: false,
+        };
+
+        for track in s.split(',').map(|t| t.trim()).filter(|t| !t.is_empty()) {
+            match track {
+                "reference" => tracks.reference = true,
+                "variants" => tracks.variants = true,
+                "alignments" => tracks.alignments = true,
+                "coverage" => tracks.coverage = true,
+                _ => return Err(format!("unknown track '{}'", track)),
+            }
+        }
+
+        Ok(tracks)
+    }
+}
+
+/// All data of a region in a single response. Tracks that were not requested are left out.
+/// Regions that are too large to show single bases only contain an overview.
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct RegionData {
+    pub(crate) region: Region,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) reference: Option<Vec<Nucleobase>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) variants: Option<Vec<Variant>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) bases: Option<Vec<AlignmentNucleobase>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) matches: Option<Vec<AlignmentMatch>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) alignments: Option<ColumnarAlignments>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) groups: Option<Vec<GroupSeparator>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) coverage: Option<Vec<Coverage>>,
+    // Aggregated modified bases, when reads are coloured by modification
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) methylation: Option<Vec<MethylationLevel>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) overview: Option<Vec<Bin>>,
+    pub(crate) level_of_detail: LevelOfDetail,
+}
+
+impl RegionData {
+    pub fn fetch(
+        pool: &ReaderPool,
+        caches: &Caches,
+        layouts: &Layouts,
+        session: Option<&str>,
+        level_of_detail: LevelOfDetail,
+        region: &Region,
+        tracks: Tracks,
+        order: &ReadOrder,
+    ) -> Result<RegionData, Box<dyn Error>> {
+        if level_of_detail.is_overview(region.from, region.to) {
+            return Ok(RegionData {
+                region: region.clone(),
+                reference: None,
+                variants: None,
+                bases: None,
+                matches: None,
+                alignments: None,
+                groups: None,
+                coverage: None,
+                methylation: None,
+                overview: if tracks.has_overview() {
+                    Some(caches.overview(pool, region, level_of_detail.bins)?)
+                } else {
+                    None
+                },
+                level_of_detail: level_of_detail,
+            });
+        }
+
+        let (bases, matches, groups) = if tracks.alignments {
+            let (mut bases, mut matches, infos) = caches.alignments(pool, region)?;
+            let groups = layouts.assign_ordered(
+                session,
+                &region.chromosome,
+                &mut bases,
+                &mut matches,
+                &infos,
+                order,
+            );
+            let groups = if groups.is_empty() {
+                None
+            } else {
+                Some(groups)
+            };
+            (Some(bases), Some(matches), groups)
+        } else {
+            (None, None, None)
+        };
+
+        Ok(RegionData {
+            region: region.clone(),
+            reference: if tracks.reference {
+                Some(caches.reference(pool, region)?)
+            } else {
+                None
+            },
+            variants: if tracks.variants {
+                Some(caches.variants(pool, region)?)
+            } else {
+                None
+            },
+            bases: bases,
+            matches: matches,
+            alignments: None,
+            groups: groups,
+            coverage: if tracks.coverage {
+                Some(caches.coverage(pool, region)?)
+            } else {
+                None
+            },
+            methylation: None,
+            overview: None,
+            level_of_detail: level_of_detail,
+        })
+    }
+
+    /// Leaves out the soft clipped overhangs of the reads.
+    pub fn hide_soft_clips(&mut self) {
+        if let Some(bases) = self.bases.as_mut() {
+            hide_soft_clips(bases);
+        }
+    }
+
+    /// Leaves out insertions and deletions shorter than `min_length` bases.
+    pub fn hide_small_indels(&mut self, min_length: u32) {
+        if let (Some(bases), Some(matches)) = (self.bases.as_mut(), self.matches.as_mut()) {
+            hide_small_indels(bases, matches, min_length);
+        }
+    }
+
+    /// Keeps only the reads that support the alternative allele of a variant in the region. The
+    /// emptied rows are removed unless the reads are in the shared layout of the default order.
+    pub fn keep_alt_reads(&mut self, order: &ReadOrder) {
+        if let (Some(bases), Some(matches)) = (self.bases.as_mut(), self.matches.as_mut()) {
+            let mut groups = self.groups.take().unwrap_or_default();
+            keep_alt_reads(bases, matches, &mut groups, order);
+            self.groups = if groups.is_empty() {
+                None
+            } else {
+                Some(groups)
+            };
+        }
+    }
+
+    /// Keeps the modified bases of the given type and aggregates them per position. Without a
+    /// type, all modified bases are left out.
+    pub fn select_modifications(&mut self, modification: Option<ModificationType>) {
+        if let Some(bases) = self.bases.as_mut() {
+            select_modifications(bases, modification);
+            self.methylation = modification.map(|m| read_methylation_levels(bases, m));
+        }
+    }
+
+    /// Replaces the bases and matches by a columnar read and event table.
+    pub fn into_columnar(mut self) -> RegionData {
+        if let (Some(bases), Some(matches)) = (self.bases.take(), self.matches.take()) {
+            self.alignments = Some(ColumnarAlignments::new(&bases, &matches));
+        }
+
+        self
+    }
+}
@@ -0,0 +1,250 @@
+use super::*;
+use cache::Caches;
+use layout::Layouts;
+use name_index::{LocusType, NameIndex};
+use overview::fetch_overview;
+use readers::ReaderPool;
+use std::path::Path;
+
+#[test]
+fn region_parse_test() {
+    let region = Region::parse("chr1:1,000-2,000").unwrap();
+
+    assert_eq!(region, Region::new(String::from("chr1"), 999, 2000));
+    assert_eq!(region.to_string(), "chr1:1000-2000");
+}
+
+#[test]
+fn single_position_region_test() {
+    let region = Region::parse("11:887344").unwrap();
+
+    assert_eq!(region, Region::new(String::from("11"), 887343, 887344));
+}
+
+#[test]
+fn invalid_region_test() {
+    assert!(Region::parse("chr1").is_err());
+    assert!(Region::parse("chr1:2000-1000").is_err());
+    assert!(Region::parse("chr1:0-10").is_err());
+}
+
+#[test]
+fn search_test() {
+    let index = NameIndex::build(
+        Some(Path::new("tests/resources/genes.bed")),
+        Path::new("tests/resources/report-test.vcf.gz"),
+    )
+    .unwrap();
+
+    let genes = index.search("c1qb");
+    assert_eq!(genes.len(), 1);
+    assert_eq!(genes[0].locus_type, LocusType::Gene);
+    assert_eq!(genes[0].region, Region::new(String::from("chr1"), 60, 110));
+
+    let variants = index.search("rs377549148");
+    assert_eq!(variants.len(), 1);
+    assert_eq!(variants[0].locus_type, LocusType::Variant);
+    assert_eq!(variants[0].region, Region::new(String::from("chr1"), 4, 5));
+
+    let regions = index.search("chr1:10-20");
+    assert_eq!(regions[0].region, Region::new(String::from("chr1"), 9, 20));
+
+    assert!(index.search("BRCA2").is_empty());
+}
+
+#[test]
+fn read_bed_test() {
+    let regions = read_bed(Path::new("tests/resources/genes.bed")).unwrap();
+
+    assert_eq!(
+        regions,
+        vec![
+            Region::new(String::from("chr1"), 0, 40),
+            Region::new(String::from("chr1"), 60, 110),
+        ]
+    );
+    assert_eq!(regions[1].file_name(), "chr1_61-110");
+}
+
+#[test]
+fn region_data_test() {
+    let pool = ReaderPool::new(
+        Path::new("tests/resources/ref.fa"),
+        Path::new("tests/resources/test.bam"),
+        Path::new("tests/resources/report-test.vcf.gz"),
+    );
+    let caches = Caches::new(4, None);
+    let layouts = Layouts::new(4);
+    let region = Region::new(String::from("chr1"), 0, 60);
+
+    let tracks = Tracks::from_str("reference,coverage").unwrap();
+    let data = RegionData::fetch(
+        &pool,
+        &caches,
+        &layouts,
+        None,
+        LevelOfDetail::default(),
+        &region,
+        tracks,
+        &ReadOrder::default(),
+    )
+    .unwrap();
+    assert_eq!(data.reference.unwrap().len(), 60);
+    assert_eq!(data.coverage.unwrap().len(), 60);
+    assert!(data.variants.is_none());
+    assert!(data.bases.is_none());
+
+    let data = RegionData::fetch(
+        &pool,
+        &caches,
+        &layouts,
+        None,
+        LevelOfDetail::default(),
+        &region,
+        Tracks::default(),
+        &ReadOrder::default(),
+    )
+    .unwrap();
+    assert_eq!(data.variants.unwrap().len(), 2);
+    assert_eq!(data.matches.unwrap().len(), 3);
+
+    assert!(Tracks::from_str("reference,genes").is_err());
+}
+
+#[test]
+fn overview_test() {
+    let pool = ReaderPool::new(
+        Path::new("tests/resources/ref.fa"),
+        Path::new("tests/resources/test.bam"),
+        Path::new("tests/resources/report-test.vcf.gz"),
+    );
+    let caches = Caches::new(4, None);
+    let level_of_detail = LevelOfDetail {
+        max_detail_span: 50,
+        bins: 10,
+    };
+    let region = Region::new(String::from("chr1"), 0, 100);
+
+    let data = RegionData::fetch(
+        &pool,
+        &caches,
+        &Layouts::new(4),
+        None,
+        level_of_detail,
+        &region,
+        Tracks::default(),
+        &ReadOrder::default(),
+    )
+    .unwrap();
+    assert!(data.bases.is_none());
+    assert!(data.reference.is_none());
+
+    let overview = data.overview.unwrap();
+    assert_eq!(overview.len(), 10);
+    assert_eq!(overview[0].start_position, -0.5);
+    assert_eq!(overview[9].end_position, 99.5);
+    assert_eq!(overview[0].reads, 1);
+    assert_eq!(overview[0].coverage, 0.6);
+    assert_eq!(overview.iter().map(|b| b.variants).sum::<u32>(), 2);
+
+    // Reads of large bins are only fetched at their start and counted for the whole bin
+    let mut readers = pool.get().unwrap();
+    let readers = &mut *readers;
+    let overview = fetch_overview(
+        &mut readers.bam,
+        &mut readers.vcf,
+        String::from("chr1"),
+        0,
+        20000,
+        2,
+    )
+    .unwrap();
+    assert_eq!(overview[0].reads, 10);
+    assert_eq!(overview[0].coverage, 0.098);
+    assert_eq!(overview[0].variants, 2);
+    assert_eq!(overview[1].reads, 0);
+
+    // Only the reference was requested, which is not shown for large regions
+    let data = RegionData::fetch(
+        &pool,
+        &caches,
+        &Layouts::new(4),
+        None,
+        level_of_detail,
+        &region,
+        Tracks::from_str("reference").unwrap(),
+        &ReadOrder::default(),
+    )
+    .unwrap();
+    assert!(data.overview.is_none());
+
+    // Single reads of large regions are not returned by the alignment endpoint
+    assert!(level_of_detail.check_detail_span(0, 100).is_err());
+    assert!(level_of_detail.check_detail_span(0, 50).is_ok());
+}
+
+#[test]
+fn catalogue_test() {
+    let resources = fs::canonicalize("tests/resources").unwrap();
+    let dir = std::env::temp_dir().join(format!("catalogue-test-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let catalogue = dir.join("catalogue.toml");
+
+    let entry = |name: &str, description: &str| {
+        format!(
+            "[datasets.{}]\nreference = \"{}\"\nalignments = \"{}\"\nvariants = \"{}\"\ndescription = \"{}\"\n",
+            name,
+            resources.join("ref.fa").display(),
+            resources.join("test.bam").display(),
+            resources.join("report-test.vcf.gz").display(),
+            description
+        )
+    };
+
+    fs::write(&catalogue, entry("first", "First dataset")).unwrap();
+    let datasets = Datasets::from_catalogue(&catalogue, 4).unwrap();
+    assert_eq!(
+        datasets.list(),
+        vec![DatasetInfo {
+            name: String::from("first"),
+            description: Some(String::from("First dataset")),
+        }]
+    );
+    let first = datasets.get("first").unwrap();
+    assert!(datasets.get(DEFAULT_DATASET).is_none());
+
+    // Changes are noticed by the next request and loaded in the background
+    let reload = |contents: String| {
+        fs::write(&catalogue, contents).unwrap();
+        datasets.list();
+        datasets.wait_for_reload();
+    };
+
+    reload(entry("first", "First dataset") + &entry("second", "The second dataset"));
+    let names: Vec<String> = datasets.list().into_iter().map(|d| d.name).collect();
+    assert_eq!(names, vec!["first", "second"]);
+    // Unchanged datasets keep their open files and caches
+    assert!(Arc::ptr_eq(&first, &datasets.get("first").unwrap()));
+
+    // A changed dataset that can't be opened keeps its previous version
+    reload(
+        entry("first", "Moved dataset").replace("test.bam", "missing.bam")
+            + &entry("second", "The second dataset"),
+    );
+    assert!(Arc::ptr_eq(&first, &datasets.get("first").unwrap()));
+    assert_eq!(
+        datasets.list()[0].description,
+        Some(String::from("First dataset"))
+    );
+    assert!(datasets.get("second").is_some());
+
+    // A rewrite of the same length is noticed, too
+    reload(entry("first", "Other dataset") + &entry("second", "The second dataset"));
+    reload(entry("first", "Third dataset") + &entry("second", "The second dataset"));
+    assert_eq!(
+        datasets.list()[0].description,
+        Some(String::from("Third dataset"))
+    );
+
+    fs::remove_dir_all(&dir).unwrap();
+}
@@ -1,12 +1,18 @@
-use fasta_reader::{get_fasta_length, read_fasta};
-use json_generator::manipulate_json;
+use alignment_reader::Marker;
+use allele_support::{count_support, SupportCounts};
+use fasta_reader::{fetch_fasta, get_fasta_length};
+use grouping::ReadOrder;
+use json_generator::{group_separator, manipulate_json};
+use pairs::{InsertSizeRange, INSERT_SIZE_SAMPLE};
+use readers::Readers;
 use rust_htslib::bcf::Read;
 use rustc_serialize::json::Json;
 use serde_json::Value;
-use static_reader::{get_static_reads, StaticVariant};
+use static_reader::{fetch_static_reads, StaticVariant};
 use std::error::Error;
 use std::path::Path;
-use variant_reader::VariantType;
+use theme::PlotOptions;
+use variant_reader::{VariantAlleles, VariantType};
 
 #[derive(Serialize, Clone, Debug, PartialEq)]
 pub struct Report {
@@ -18,6 +24,8 @@ pub struct Report {
     pub(crate) alternatives: Option<String>,
     pub(crate) ann: Option<Vec<Vec<String>>>,
     pub(crate) vis: String,
+    // Reads that support the reference or the alternative allele, unless it is symbolic
+    pub(crate) support: Option<SupportCounts>,
 }
 
 pub(crate) fn make_report(
@@ -25,9 +33,14 @@ pub(crate) fn make_report(
     fasta_path: &Path,
     bam_path: &Path,
     chrom: String,
+    specs: &str,
+    order: &ReadOrder,
 ) -> Result<Vec<Report>, Box<dyn Error>> {
     let mut vcf = rust_htslib::bcf::Reader::from_path(&vcf_path).unwrap();
     let header = vcf.header().clone();
+    let insert_sizes = InsertSizeRange::estimate(bam_path, INSERT_SIZE_SAMPLE)?;
+    // Opened once for the reads and read support of all variants
+    let mut readers = Readers::open(fasta_path, bam_path, vcf_path)?;
 
     let mut reports = Vec::new();
 
@@ -82,6 +95,7 @@ pub(crate) fn make_report(
                 let alternatives: Option<String>;
                 let end_position: f64;
                 let plot_start_position;
+                let support: Option<SupportCounts>;
 
                 match alt {
                     b"<DEL>" => {
@@ -89,6 +103,7 @@ pub(crate) fn make_report(
                         alternatives = None;
                         end_position = end_pos.unwrap();
                         plot_start_position = pos as f64 - 0.5;
+                        support = None;
                     }
                     b"<INV>" => {
                         var_type = VariantType::Inversion;
@@ -96,6 +111,7 @@ pub(crate) fn make_report(
                         alternatives = Some(rev.clone());
                         end_position = end_pos.unwrap();
                         plot_start_position = pos as f64 - 0.5;
+                        support = None;
                     }
                     b"<DUP>" => {
                         var_type = VariantType::Duplicate;
@@ -103,8 +119,19 @@ pub(crate) fn make_report(
                         alternatives = Some(dup.clone());
                         end_position = end_pos.unwrap();
                         plot_start_position = pos as f64 - 0.5;
+                        support = None;
                     }
                     _ => {
+                        support = match VariantAlleles::new(pos, alleles[0], alt) {
+                            Some(v) => Some(count_support(
+                                &mut readers.bam,
+                                &mut readers.fasta,
+                                chrom.clone(),
+                                &v,
+                            )?),
+                            None => None,
+                        };
+
                         let mut allel = String::from("");
 
                         for c in alt {
@@ -151,38 +178,54 @@ pub(crate) fn make_report(
 
                 if variant.pos() < 75 {
                     let content = create_report_data(
-                        fasta_path,
+                        &mut readers,
                         var.clone(),
-                        bam_path,
                         chrom.clone(),
                         0,
                         end_position as u64 + 75,
+                        order,
+                        insert_sizes.as_ref(),
+                    )?;
+                    visualization = manipulate_json(
+                        content,
+                        0,
+                        end_position as u64 + 75,
+                        specs,
+                        &PlotOptions::default(),
                     );
-                    visualization = manipulate_json(content, 0, end_position as u64 + 75);
                 } else if variant.pos() + 75 >= fasta_length as i64 {
                     let content = create_report_data(
-                        fasta_path,
+                        &mut readers,
                         var.clone(),
-                        bam_path,
                         chrom.clone(),
                         variant.pos() as u64 - 75,
                         fasta_length - 1,
+                        order,
+                        insert_sizes.as_ref(),
+                    )?;
+                    visualization = manipulate_json(
+                        content,
+                        variant.pos() as u64 - 75,
+                        fasta_length - 1,
+                        specs,
+                        &PlotOptions::default(),
                     );
-                    visualization =
-                        manipulate_json(content, variant.pos() as u64 - 75, fasta_length - 1);
                 } else {
                     let content = create_report_data(
-                        fasta_path,
+                        &mut readers,
                         var.clone(),
-                        bam_path,
                         chrom.clone(),
                         variant.pos() as u64 - 75,
                         end_position as u64 + 75,
-                    );
+                        order,
+                        insert_sizes.as_ref(),
+                    )?;
                     visualization = manipulate_json(
                         content,
                         variant.pos() as u64 - 75,
                         end_position as u64 + 75,
+                        specs,
+                        &PlotOptions::default(),
                     );
                 }
 
@@ -195,6 +238,7 @@ pub(crate) fn make_report(
                     alternatives: var.alternatives,
                     ann: Some(ann_strings.clone()),
                     vis: visualization.to_string(),
+                    support: support,
                 };
 
                 reports.push(r);
@@ -206,21 +250,34 @@ pub(crate) fn make_report(
 }
 
 pub fn create_report_data(
-    fasta_path: &Path,
+    readers: &mut Readers,
     variant: StaticVariant,
-    bam_path: &Path,
     chrom: String,
     from: u64,
     to: u64,
-) -> Json {
+    order: &ReadOrder,
+    insert_sizes: Option<&InsertSizeRange>,
+) -> Result<Json, Box<dyn Error>> {
     let mut data = Vec::new();
 
-    for f in read_fasta(fasta_path.clone(), chrom.clone(), from, to) {
+    let reference = fetch_fasta(&mut readers.fasta, chrom.clone(), from, to)?;
+    for f in reference.iter() {
         let nucleobase = json!(f);
         data.push(nucleobase);
     }
 
-    let (bases, matches) = get_static_reads(bam_path, fasta_path, chrom.clone(), from, to);
+    let (mut bases, matches, groups) = fetch_static_reads(
+        readers,
+        &reference,
+        chrom.clone(),
+        from,
+        to,
+        order,
+        insert_sizes,
+        0,
+    )?;
+    // Reports don't colour reads by modification
+    bases.retain(|b| b.marker_type != Marker::Modification);
 
     for b in bases {
         let base = json!(b);
@@ -232,9 +289,13 @@ pub fn create_report_data(
         data.push(mat);
     }
 
+    for g in groups.iter() {
+        data.push(group_separator(g));
+    }
+
     data.push(json!(variant));
 
-    let values = Json::from_str(&json!(data).to_string()).unwrap();
+    let values = Json::from_str(&json!(data).to_string())?;
 
-    values
+    Ok(values)
 }
@@ -1,28 +1,48 @@
-use alignment_reader::Marker;
-use alignment_reader::{make_nucleobases, read_indexed_bam, AlignmentMatch, AlignmentNucleobase};
+use alignment_reader::{
+    classify_insert_sizes, fetch_alignments, hide_small_indels, make_nucleobases, read_infos,
+    AlignmentMatch, AlignmentNucleobase, Marker, Strand,
+};
+use allele_support::{assign_alleles, Allele};
+use fasta_reader::{fetch_fasta, Nucleobase};
+use grouping::{GroupSeparator, ReadInfos, ReadOrder};
+use layout::{assign_grouped, compact_rows, RowLayout};
+use modifications::{methylation_levels, MethylationLevel, ModificationType};
+use pairs::{InsertSizeRange, PairClass};
+use readers::Readers;
+use rust_htslib::bcf;
 use std::collections::BTreeMap;
-use std::path::Path;
-use variant_reader::{read_indexed_vcf, Variant, VariantType};
+use std::error::Error;
+use variant_reader::{fetch_variants, Variant, VariantType};
 
 #[derive(Serialize, Clone, Debug)]
 pub struct StaticAlignmentMatch {
-    marker_type: Marker,
-    start_position: f64,
-    end_position: f64,
-    flags: BTreeMap<u16, &'static str>,
-    name: String,
-    row: u8,
+    pub(crate) marker_type: Marker,
+    pub(crate) start_position: f64,
+    pub(crate) end_position: f64,
+    pub(crate) flags: BTreeMap<u16, &'static str>,
+    pub(crate) name: String,
+    pub(crate) row: u32,
+    pub(crate) pair_class: PairClass,
+    pub(crate) strand: Strand,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) allele: Option<Allele>,
 }
 
 #[derive(Serialize, Clone)]
 pub struct StaticAlignmentNucleobase {
-    marker_type: Marker,
-    bases: String,
-    start_position: f64,
-    end_position: f64,
-    flags: BTreeMap<u16, &'static str>,
-    name: String,
-    row: u8,
+    pub(crate) marker_type: Marker,
+    pub(crate) bases: String,
+    pub(crate) start_position: f64,
+    pub(crate) end_position: f64,
+    pub(crate) flags: BTreeMap<u16, &'static str>,
+    pub(crate) name: String,
+    pub(crate) row: u32,
+    pub(crate) pair_class: PairClass,
+    pub(crate) strand: Strand,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) probability: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) allele: Option<Allele>,
 }
 
 #[derive(Serialize, Clone)]
@@ -36,6 +56,93 @@ pub struct StaticVariant {
     pub(crate) var_type: VariantType,
 }
 
+/// Everything that is shown in a static plot of one region.
+pub struct StaticData {
+    pub(crate) reference: Vec<Nucleobase>,
+    pub(crate) bases: Vec<StaticAlignmentNucleobase>,
+    pub(crate) matches: Vec<StaticAlignmentMatch>,
+    pub(crate) variants: Vec<StaticVariant>,
+    pub(crate) groups: Vec<GroupSeparator>,
+    // Aggregated modified bases, when reads are coloured by modification
+    pub(crate) methylation: Vec<MethylationLevel>,
+}
+
+impl StaticData {
+    /// Fetches everything in a region. Pairs are classified by their insert size if the expected
+    /// insert sizes are known, and insertions and deletions shorter than `min_indel_length` are
+    /// hidden.
+    pub fn fetch(
+        readers: &mut Readers,
+        chrom: String,
+        from: u64,
+        to: u64,
+        order: &ReadOrder,
+        insert_sizes: Option<&InsertSizeRange>,
+        min_indel_length: u32,
+    ) -> Result<StaticData, Box<dyn Error>> {
+        let reference = fetch_fasta(&mut readers.fasta, chrom.clone(), from, to)?;
+        let (bases, matches, groups) = fetch_static_reads(
+            readers,
+            &reference,
+            chrom.clone(),
+            from,
+            to,
+            order,
+            insert_sizes,
+            min_indel_length,
+        )?;
+        let variants = fetch_static_variants(&mut readers.vcf, chrom, from, to)?;
+
+        Ok(StaticData {
+            reference: reference,
+            bases: bases,
+            matches: matches,
+            variants: variants,
+            groups: groups,
+            methylation: Vec::new(),
+        })
+    }
+
+    /// Leaves out the soft clipped overhangs of the reads.
+    pub fn hide_soft_clips(&mut self) {
+        self.bases.retain(|b| b.marker_type != Marker::SoftClip);
+    }
+
+    /// Keeps only the reads that support the alternative allele of a variant in the region. The
+    /// emptied rows are removed.
+    pub fn keep_alt_reads(&mut self) {
+        self.bases.retain(|b| b.allele == Some(Allele::Alt));
+        self.matches.retain(|m| m.allele == Some(Allele::Alt));
+
+        compact_rows(
+            self.bases
+                .iter_mut()
+                .map(|b| &mut b.row)
+                .chain(self.matches.iter_mut().map(|m| &mut m.row))
+                .collect(),
+            &mut self.groups,
+        );
+    }
+
+    /// Keeps the modified bases of the given type and aggregates them per position. Without a
+    /// type, all modified bases are left out.
+    pub fn select_modifications(&mut self, modification: Option<ModificationType>) {
+        self.bases.retain(|b| {
+            b.marker_type != Marker::Modification
+                || modification.map_or(false, |m| b.bases == m.label())
+        });
+
+        if let Some(modification) = modification {
+            let calls = self
+                .bases
+                .iter()
+                .filter(|b| b.marker_type == Marker::Modification)
+                .map(|b| (b.start_position, b.probability.unwrap_or(1.0)));
+            self.methylation = methylation_levels(calls, modification);
+        }
+    }
+}
+
 pub fn decode_static_flags(flag_vec: Vec<u16>) -> BTreeMap<u16, &'static str> {
     let mut string_map = BTreeMap::new();
 
@@ -78,93 +185,87 @@ pub fn decode_static_flags(flag_vec: Vec<u16>) -> BTreeMap<u16, &'static str> {
     string_map
 }
 
-fn calc_rows(
-    reads: Vec<AlignmentNucleobase>,
-    matches: Vec<AlignmentMatch>,
-) -> (Vec<StaticAlignmentNucleobase>, Vec<StaticAlignmentMatch>) {
-    let mut row_ends = vec![0; 30];
-
-    let mut read_names: BTreeMap<String, u8> = BTreeMap::new();
-
-    let mut reads_wr: Vec<StaticAlignmentNucleobase> = Vec::new();
-    let mut matches_wr: Vec<StaticAlignmentMatch> = Vec::new();
-
-    for r in matches {
-        let mut row: u8 = 0;
-
-        if read_names.contains_key(&r.name) {
-            row = *read_names.get(&r.name).unwrap();
-        } else {
-            for i in 1..30 {
-                if r.read_start > row_ends[i] {
-                    row = i as u8;
-                    row_ends[i] = r.read_end;
-                    read_names.insert(r.name.clone(), i as u8);
-                    break;
-                }
-            }
-        }
-
-        let f = decode_static_flags(r.flags);
-
-        let base = StaticAlignmentMatch {
+fn make_static_reads(
+    mut reads: Vec<AlignmentNucleobase>,
+    mut matches: Vec<AlignmentMatch>,
+    infos: &ReadInfos,
+    order: &ReadOrder,
+) -> (
+    Vec<StaticAlignmentNucleobase>,
+    Vec<StaticAlignmentMatch>,
+    Vec<GroupSeparator>,
+) {
+    let groups = if order.is_default() {
+        RowLayout::new().assign(&mut reads, &mut matches);
+        Vec::new()
+    } else {
+        assign_grouped(&mut reads, &mut matches, infos, order)
+    };
+
+    let matches_wr = matches
+        .into_iter()
+        .map(|r| StaticAlignmentMatch {
             marker_type: r.marker_type,
             start_position: r.start_position,
             end_position: r.end_position,
-            flags: f,
+            flags: decode_static_flags(r.flags),
             name: r.name,
-            row: row,
-        };
-
-        matches_wr.push(base);
-    }
-
-    for r in reads {
-        let mut row: u8 = 0;
-
-        if read_names.contains_key(&r.name) {
-            row = *read_names.get(&r.name).unwrap();
-        } else {
-            for i in 1..30 {
-                if r.read_start > row_ends[i] {
-                    row = i as u8;
-                    row_ends[i] = r.read_end;
-                    read_names.insert(r.name.clone(), i as u8);
-                    break;
-                }
-            }
-        }
-
-        let f = decode_static_flags(r.flags);
-
-        let base = StaticAlignmentNucleobase {
+            row: r.row,
+            pair_class: r.pair_class,
+            strand: r.strand,
+            allele: r.allele,
+        })
+        .collect();
+
+    let reads_wr = reads
+        .into_iter()
+        .map(|r| StaticAlignmentNucleobase {
             marker_type: r.marker_type,
             bases: r.bases,
             start_position: r.start_position,
             end_position: r.end_position,
-            flags: f,
+            flags: decode_static_flags(r.flags),
             name: r.name,
-            row: row,
-        };
-
-        reads_wr.push(base);
-    }
-
-    (reads_wr, matches_wr)
+            row: r.row,
+            pair_class: r.pair_class,
+            strand: r.strand,
+            probability: r.probability,
+            allele: r.allele,
+        })
+        .collect();
+
+    (reads_wr, matches_wr, groups)
 }
 
-pub fn get_static_reads(
-    path: &Path,
-    fasta_path: &Path,
+pub fn fetch_static_reads(
+    readers: &mut Readers,
+    ref_bases: &[Nucleobase],
     chrom: String,
     from: u64,
     to: u64,
-) -> (Vec<StaticAlignmentNucleobase>, Vec<StaticAlignmentMatch>) {
-    let alignments = read_indexed_bam(path, chrom.clone(), from, to);
-    let (msm, m) = make_nucleobases(fasta_path, chrom, alignments, from, to);
-    let static_bases = calc_rows(msm, m);
+    order: &ReadOrder,
+    insert_sizes: Option<&InsertSizeRange>,
+    min_indel_length: u32,
+) -> Result<
+    (
+        Vec<StaticAlignmentNucleobase>,
+        Vec<StaticAlignmentMatch>,
+        Vec<GroupSeparator>,
+    ),
+    Box<dyn Error>,
+> {
+    let mut alignments = fetch_alignments(&mut readers.bam, chrom.clone(), from, to)?;
+    if let Some(insert_sizes) = insert_sizes {
+        classify_insert_sizes(&mut alignments, insert_sizes);
+    }
+    assign_alleles(readers, &mut alignments, &chrom, from, to)?;
+    let infos = read_infos(&alignments);
+    let (mut msm, mut m) = make_nucleobases(ref_bases, alignments, from, to);
+    if min_indel_length > 0 {
+        hide_small_indels(&mut msm, &mut m, min_indel_length);
+    }
 
-    static_bases
+    Ok(make_static_reads(msm, m, &infos, order))
 }
 
 fn calc_variant_rows(variants: Vec<Variant>) -> Vec<StaticVariant> {
@@ -198,9 +299,13 @@ fn calc_variant_rows(variants: Vec<Variant>) -> Vec<StaticVariant> {
     vars
 }
 
-pub fn get_static_variants(path: &Path, chrom: String, from: u64, to: u64) -> Vec<StaticVariant> {
-    let variants = read_indexed_vcf(path, chrom, from, to);
-    let static_variants = calc_variant_rows(variants);
+pub fn fetch_static_variants(
+    vcf: &mut bcf::IndexedReader,
+    chrom: String,
+    from: u64,
+    to: u64,
+) -> Result<Vec<StaticVariant>, Box<dyn Error>> {
+    let variants = fetch_variants(vcf, chrom, from, to)?;
 
-    static_variants
+    Ok(calc_variant_rows(variants))
 }
@@ -0,0 +1,363 @@
+use alignment_reader::Marker;
+use allele_support::Allele;
+use region::Region;
+use static_reader::StaticData;
+use std::collections::BTreeSet;
+use std::fmt::Write;
+use theme::{PlotOptions, MARKER_TYPES};
+
+const WIDTH: f64 = 700.0;
+const ROW_HEIGHT: f64 = 10.0;
+const MARGIN_LEFT: f64 = 10.0;
+const MARGIN_TOP: f64 = 30.0;
+const MARGIN_BOTTOM: f64 = 45.0;
+const LEGEND_WIDTH: f64 = 110.0;
+// Same row as in the vega specs, above the variants
+const METHYLATION_ROW: i16 = -10;
+
+struct Mark {
+    marker_type: String,
+    color_key: String,
+    start_position: f64,
+    end_position: f64,
+    row: i16,
+    title: String,
+    // Soft-masked reference bases are shaded
+    faded: bool,
+}
+
+/// Renders the reference, read and variant tracks of a region to a standalone svg image,
+/// using the same layout and colors as the vega specs.
+pub fn render_svg(data: &StaticData, region: &Region, options: &PlotOptions) -> String {
+    let from = region.from;
+    let to = region.to.max(from + 1);
+    let marks = collect_marks(data, options);
+
+    let rows: BTreeSet<i16> = marks
+        .iter()
+        .map(|m| m.row)
+        .chain(data.groups.iter().map(|g| g.row as i16))
+        .collect();
+    let rows: Vec<i16> = rows.into_iter().collect();
+
+    let row_height = match options.height {
+        Some(height) => height as f64 / rows.len().max(1) as f64,
+        None => ROW_HEIGHT,
+    };
+    // Strokes are thinned to leave the requested space between rows
+    let band = row_height * (1.0 - options.row_spacing.unwrap_or(0.0));
+    let plot_height = rows.len().max(1) as f64 * row_height;
+    let total_width = MARGIN_LEFT + WIDTH + LEGEND_WIDTH;
+    let total_height = MARGIN_TOP + plot_height + MARGIN_BOTTOM;
+
+    let domain_start = from as f64 - 0.5;
+    let domain_end = to as f64 - 0.5;
+    let x = |pos: f64| MARGIN_LEFT + (pos - domain_start) / (domain_end - domain_start) * WIDTH;
+    let y = |row: i16| {
+        let i = rows.iter().position(|r| *r == row).unwrap() as f64;
+        MARGIN_TOP + (i + 0.5) * row_height
+    };
+
+    let mut svg = String::new();
+
+    writeln!(
+        svg,
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{}\" height=\"{}\" viewBox=\"0 0 {} {}\" font-family=\"sans-serif\" font-size=\"10\">",
+        total_width, total_height, total_width, total_height
+    )
+    .unwrap();
+    writeln!(
+        svg,
+        "<rect width=\"{}\" height=\"{}\" fill=\"white\"/>",
+        total_width, total_height
+    )
+    .unwrap();
+    writeln!(
+        svg,
+        "<defs><clipPath id=\"plot\"><rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\"/></clipPath></defs>",
+        MARGIN_LEFT, MARGIN_TOP, WIDTH, plot_height
+    )
+    .unwrap();
+
+    writeln!(
+        svg,
+        "<text x=\"{}\" y=\"{}\" text-anchor=\"middle\" font-size=\"13\" font-weight=\"bold\">{}</text>",
+        MARGIN_LEFT + WIDTH / 2.0,
+        MARGIN_TOP - 12.0,
+        escape(&region.to_string())
+    )
+    .unwrap();
+
+    // Axis with grid lines
+    let step = tick_step(to - from);
+    let first_tick = (from + step - 1) / step * step;
+    let axis_y = MARGIN_TOP + plot_height;
+    writeln!(svg, "<g stroke=\"#dddddd\">").unwrap();
+    let mut tick = first_tick;
+    while tick < to {
+        let tx = x(tick as f64);
+        writeln!(
+            svg,
+            "<line x1=\"{:.2}\" y1=\"{}\" x2=\"{:.2}\" y2=\"{}\"/>",
+            tx, MARGIN_TOP, tx, axis_y
+        )
+        .unwrap();
+        tick += step;
+    }
+    writeln!(svg, "</g>").unwrap();
+
+    writeln!(
+        svg,
+        "<line x1=\"{}\" y1=\"{}\" x2=\"{}\" y2=\"{}\" stroke=\"#888888\"/>",
+        MARGIN_LEFT,
+        axis_y,
+        MARGIN_LEFT + WIDTH,
+        axis_y
+    )
+    .unwrap();
+    let mut tick = first_tick;
+    while tick < to {
+        let tx = x(tick as f64);
+        writeln!(
+            svg,
+            "<line x1=\"{:.2}\" y1=\"{}\" x2=\"{:.2}\" y2=\"{}\" stroke=\"#888888\"/><text x=\"{:.2}\" y=\"{}\" text-anchor=\"middle\">{}</text>",
+            tx,
+            axis_y,
+            tx,
+            axis_y + 5.0,
+            tx,
+            axis_y + 15.0,
+            tick
+        )
+        .unwrap();
+        tick += step;
+    }
+    writeln!(
+        svg,
+        "<text x=\"{}\" y=\"{}\" text-anchor=\"middle\" font-weight=\"bold\">Position</text>",
+        MARGIN_LEFT + WIDTH / 2.0,
+        axis_y + 32.0
+    )
+    .unwrap();
+
+    // Marks, drawn in the order of the z scale of the vega specs
+    writeln!(svg, "<g clip-path=\"url(#plot)\" stroke-opacity=\"0.8\">").unwrap();
+    let mut sorted_marks: Vec<&Mark> = marks.iter().collect();
+    sorted_marks.sort_by_key(|m| z_index(&m.marker_type));
+    for m in sorted_marks {
+        let my = y(m.row);
+        writeln!(
+            svg,
+            "<line x1=\"{:.2}\" y1=\"{:.2}\" x2=\"{:.2}\" y2=\"{:.2}\" stroke=\"{}\" stroke-width=\"{}\"{}><title>{}</title></line>",
+            x(m.start_position),
+            my,
+            x(m.end_position),
+            my,
+            color(options, &m.color_key),
+            stroke_width(&m.marker_type).min(band),
+            if m.faded { " stroke-opacity=\"0.4\"" } else { "" },
+            escape(&m.title)
+        )
+        .unwrap();
+    }
+    writeln!(svg, "</g>").unwrap();
+
+    for g in data.groups.iter() {
+        let gy = y(g.row as i16);
+        writeln!(
+            svg,
+            "<line x1=\"{}\" y1=\"{:.2}\" x2=\"{}\" y2=\"{:.2}\" stroke=\"#888888\" stroke-dasharray=\"4 2\"/><text x=\"{}\" y=\"{:.2}\" fill=\"#444444\" font-weight=\"bold\">{}</text>",
+            MARGIN_LEFT,
+            gy,
+            MARGIN_LEFT + WIDTH,
+            gy,
+            MARGIN_LEFT + 2.0,
+            gy - 1.0,
+            escape(&g.group)
+        )
+        .unwrap();
+    }
+
+    // Legend with all marker types that occur in the plot
+    let legend_x = MARGIN_LEFT + WIDTH + 20.0;
+    writeln!(
+        svg,
+        "<text x=\"{}\" y=\"{}\" font-weight=\"bold\">Base</text>",
+        legend_x, MARGIN_TOP
+    )
+    .unwrap();
+    let mut legend_y = MARGIN_TOP + 14.0;
+    for marker_type in MARKER_TYPES.iter() {
+        if marks.iter().any(|m| m.color_key == *marker_type) {
+            writeln!(
+                svg,
+                "<line x1=\"{}\" y1=\"{}\" x2=\"{}\" y2=\"{}\" stroke=\"{}\" stroke-width=\"2\" stroke-opacity=\"0.8\"/><text x=\"{}\" y=\"{}\">{}</text>",
+                legend_x,
+                legend_y - 3.0,
+                legend_x + 12.0,
+                legend_y - 3.0,
+                color(options, marker_type),
+                legend_x + 18.0,
+                legend_y,
+                marker_type
+            )
+            .unwrap();
+            legend_y += 14.0;
+        }
+    }
+
+    writeln!(svg, "</svg>").unwrap();
+
+    svg
+}
+
+fn collect_marks(data: &StaticData, options: &PlotOptions) -> Vec<Mark> {
+    let mut marks = Vec::new();
+
+    for r in data.reference.iter() {
+        marks.push(Mark {
+            marker_type: r.marker_type.to_string(),
+            color_key: ambiguity_color_key(&r.marker_type.to_string()),
+            start_position: r.start_position,
+            end_position: r.end_position,
+            row: r.row as i16,
+            title: if r.soft_masked {
+                format!("{} (soft-masked)", r.marker_type)
+            } else {
+                r.marker_type.to_string()
+            },
+            faded: r.soft_masked,
+        });
+    }
+
+    for m in data.matches.iter() {
+        // Reads that support the alternative allele of a variant are highlighted
+        let color_key = if m.marker_type == Marker::Match && m.allele == Some(Allele::Alt) {
+            String::from("AltSupport")
+        } else if options.color_by_strand && m.marker_type == Marker::Match {
+            format!("{:?}Strand", m.strand)
+        } else if (m.marker_type == Marker::Match || m.marker_type == Marker::Pairing)
+            && m.pair_class.has_color()
+        {
+            format!("{:?}", m.pair_class)
+        } else {
+            format!("{:?}", m.marker_type)
+        };
+        marks.push(Mark {
+            marker_type: format!("{:?}", m.marker_type),
+            color_key: color_key,
+            start_position: m.start_position,
+            end_position: m.end_position,
+            row: m.row as i16,
+            title: match m.allele {
+                Some(allele) => format!("{} ({:?})", m.name, allele),
+                None => m.name.clone(),
+            },
+            faded: false,
+        });
+    }
+
+    for b in data.bases.iter() {
+        // Without a color scale, modified bases are only shown as modified or not
+        let color_key = match b.probability {
+            Some(probability) if probability >= 0.5 => String::from("Modified"),
+            Some(_) => String::from("Unmodified"),
+            None => ambiguity_color_key(&format!("{:?}", b.marker_type)),
+        };
+        marks.push(Mark {
+            marker_type: format!("{:?}", b.marker_type),
+            color_key: color_key,
+            start_position: b.start_position,
+            end_position: b.end_position,
+            row: b.row as i16,
+            title: format!("{} {}", b.name, b.bases),
+            faded: false,
+        });
+    }
+
+    for m in data.methylation.iter() {
+        marks.push(Mark {
+            marker_type: String::from("Methylation"),
+            color_key: String::from(if m.probability >= 0.5 {
+                "Modified"
+            } else {
+                "Unmodified"
+            }),
+            start_position: m.start_position,
+            end_position: m.end_position,
+            row: METHYLATION_ROW,
+            title: format!(
+                "{} {:.2} ({} of {} reads)",
+                m.modification, m.probability, m.modified, m.calls
+            ),
+            faded: false,
+        });
+    }
+
+    for v in data.variants.iter() {
+        let alternatives = match &v.alternatives {
+            Some(alt) => alt.clone(),
+            None => String::new(),
+        };
+        marks.push(Mark {
+            marker_type: v.marker_type.clone(),
+            color_key: format!("{:?}", v.var_type),
+            start_position: v.start_position,
+            end_position: v.end_position,
+            row: v.row as i16,
+            title: format!("{} > {}", v.reference, alternatives),
+            faded: false,
+        });
+    }
+
+    marks
+}
+
+/// IUPAC ambiguity codes are drawn in the color of N.
+fn ambiguity_color_key(base: &str) -> String {
+    match base {
+        "R" | "Y" | "S" | "W" | "K" | "M" | "B" | "D" | "H" | "V" => String::from("N"),
+        _ => base.to_owned(),
+    }
+}
+
+/// Picks a tick distance of 1, 2 or 5 times a power of ten, giving roughly ten ticks.
+fn tick_step(span: u64) -> u64 {
+    let mut step = 1;
+    loop {
+        for factor in [1, 2, 5].iter() {
+            if span / (step * factor) <= 10 {
+                return step * factor;
+            }
+        }
+        step *= 10;
+    }
+}
+
+fn color(options: &PlotOptions, marker_type: &str) -> &'static str {
+    options.theme.color(marker_type).unwrap_or("#000000")
+}
+
+fn stroke_width(marker_type: &str) -> f64 {
+    match marker_type {
+        "Insertion" | "SplitEnd" => 7.5,
+        "Pairing" | "Split" => 1.0,
+        _ => 5.0,
+    }
+}
+
+fn z_index(marker_type: &str) -> u8 {
+    match marker_type {
+        "Insertion" | "SplitEnd" => 4,
+        "Modification" => 3,
+        "A" | "C" | "G" | "T" | "N" | "Deletion" => 2,
+        _ => 1,
+    }
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
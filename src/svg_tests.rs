@@ -0,0 +1,61 @@
+use super::*;
+use std::path::Path;
+
+#[test]
+fn svg_test() {
+    let mut readers = Readers::open(
+        Path::new("tests/resources/ref.fa"),
+        Path::new("tests/resources/test.bam"),
+        Path::new("tests/resources/report-test.vcf.gz"),
+    )
+    .unwrap();
+    let region = Region::parse("chr1:1-100").unwrap();
+
+    let svg = render_static(
+        &mut readers,
+        &region,
+        "svg",
+        "",
+        &PlotOptions::default(),
+        &ReadOrder::default(),
+        None,
+    )
+    .unwrap();
+
+    assert!(svg.starts_with("<svg xmlns=\"http://www.w3.org/2000/svg\""));
+    assert!(svg.trim_end().ends_with("</svg>"));
+    assert!(svg.contains(">chr1:1-100</text>"));
+    // reference bases, the read matches and the insertion of the test read
+    assert!(svg.contains("stroke=\"#7284A8\""));
+    assert!(svg.contains("stroke=\"#BBBBBB\""));
+    assert!(svg.contains("stroke=\"#047C0A\" stroke-width=\"7.5\""));
+}
+
+#[test]
+fn svg_small_indel_test() {
+    let mut readers = Readers::open(
+        Path::new("tests/resources/ref.fa"),
+        Path::new("tests/resources/test.bam"),
+        Path::new("tests/resources/report-test.vcf.gz"),
+    )
+    .unwrap();
+    let options = PlotOptions {
+        min_indel_length: 3,
+        ..PlotOptions::default()
+    };
+
+    let svg = render_static(
+        &mut readers,
+        &Region::parse("chr1:1-100").unwrap(),
+        "svg",
+        "",
+        &options,
+        &ReadOrder::default(),
+        None,
+    )
+    .unwrap();
+
+    // The inserted bases AA of the test read are hidden
+    assert!(!svg.contains("stroke=\"#047C0A\" stroke-width=\"7.5\""));
+    assert!(svg.contains("stroke=\"#BBBBBB\""));
+}
@@ -0,0 +1,153 @@
+use modifications::ModificationType;
+use std::str::FromStr;
+
+// Domain of the color scale of the vega specs
+pub const MARKER_TYPES: [&str; 26] = [
+    "A",
+    "C",
+    "G",
+    "T",
+    "N",
+    "Variant",
+    "Insertion",
+    "Deletion",
+    "Match",
+    "Pairing",
+    "Inversion",
+    "Duplicate",
+    "InterChromosomal",
+    "LargeInsert",
+    "SmallInsert",
+    "ReverseForward",
+    "ForwardForward",
+    "ReverseReverse",
+    "SoftClip",
+    "Split",
+    "SplitEnd",
+    "Modified",
+    "Unmodified",
+    "ForwardStrand",
+    "ReverseStrand",
+    "AltSupport",
+];
+
+const DEFAULT_COLORS: [&str; 26] = [
+    "#CADB69", "#F2B671", "#F28CC2", "#7284A8", "#A23E11", "#000000", "#047C0A", "#CC1414",
+    "#BBBBBB", "#BBBBBB", "#984aff", "#ffa600", "#B15928", "#E31A1C", "#1F78B4", "#33A02C",
+    "#17BECF", "#0B5394", "#9E9AC8", "#E7298A", "#E7298A", "#B2182B", "#2166AC", "#E6969B",
+    "#969BE6", "#FF7F00",
+];
+
+// Based on the Okabe-Ito palette, with the pair classes from Paul Tol's muted palette
+const COLORBLIND_COLORS: [&str; 26] = [
+    "#009E73", "#0072B2", "#E69F00", "#D55E00", "#999999", "#000000", "#CC79A7", "#56B4E9",
+    "#DDDDDD", "#DDDDDD", "#F0E442", "#882255", "#117733", "#AA4499", "#44AA99", "#332288",
+    "#88CCEE", "#661100", "#BBBBBB", "#CC6677", "#CC6677", "#AA3377", "#4477AA", "#EE7733",
+    "#0077BB", "#EE3377",
+];
+
+const GREYSCALE_COLORS: [&str; 26] = [
+    "#404040", "#707070", "#A0A0A0", "#202020", "#C0C0C0", "#000000", "#000000", "#606060",
+    "#E0E0E0", "#E0E0E0", "#303030", "#909090", "#101010", "#505050", "#B0B0B0", "#303030",
+    "#808080", "#606060", "#C8C8C8", "#000000", "#000000", "#000000", "#D0D0D0", "#B8B8B8",
+    "#787878", "#585858",
+];
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Theme {
+    Default,
+    ColorBlind,
+    Greyscale,
+}
+
+impl Theme {
+    pub fn color(&self, marker_type: &str) -> Option<&'static str> {
+        let colors = match self {
+            Theme::Default => &DEFAULT_COLORS,
+            Theme::ColorBlind => &COLORBLIND_COLORS,
+            Theme::Greyscale => &GREYSCALE_COLORS,
+        };
+
+        MARKER_TYPES
+            .iter()
+            .position(|m| *m == marker_type)
+            .map(|i| colors[i])
+    }
+}
+
+impl FromStr for Theme {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Theme, String> {
+        match s {
+            "default" => Ok(Theme::Default),
+            "colorblind" => Ok(Theme::ColorBlind),
+            "greyscale" => Ok(Theme::Greyscale),
+            _ => Err(format!("unknown theme '{}'", s)),
+        }
+    }
+}
+
+/// Settings for the look of static plots.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PlotOptions {
+    pub(crate) theme: Theme,
+    pub(crate) height: Option<u32>,
+    pub(crate) row_spacing: Option<f64>,
+    // Show soft clipped bases as overhangs at the ends of the reads
+    pub(crate) soft_clips: bool,
+    // Hide insertions and deletions shorter than this
+    pub(crate) min_indel_length: u32,
+    // Colour matches by the strand of their read instead of grey
+    pub(crate) color_by_strand: bool,
+    // Only show reads that support the alternative allele of a variant
+    pub(crate) alt_only: bool,
+    // Colour the modified bases of this type by their probability
+    pub(crate) modification: Option<ModificationType>,
+}
+
+impl PlotOptions {
+    /// Rejects settings that can't be drawn, like a row spacing larger than the rows.
+    pub fn validate(&self) -> Result<(), String> {
+        if let Some(row_spacing) = self.row_spacing {
+            check_row_spacing(row_spacing)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// The space between rows is a fraction of the row height, from 0 up to (but not including) 1.
+pub fn check_row_spacing(row_spacing: f64) -> Result<f64, String> {
+    if row_spacing >= 0.0 && row_spacing < 1.0 {
+        Ok(row_spacing)
+    } else {
+        Err(format!(
+            "row spacing must be at least 0 and less than 1, not {}",
+            row_spacing
+        ))
+    }
+}
+
+/// Parses the row spacing option, e.g. `0.2`.
+pub fn parse_row_spacing(s: &str) -> Result<f64, String> {
+    let row_spacing =
+        f64::from_str(s).map_err(|e| format!("invalid row spacing '{}': {}", s, e))?;
+
+    check_row_spacing(row_spacing)
+}
+
+impl Default for PlotOptions {
+    fn default() -> PlotOptions {
+        PlotOptions {
+            theme: Theme::Default,
+            height: None,
+            row_spacing: None,
+            soft_clips: true,
+            min_indel_length: 0,
+            color_by_strand: false,
+            alt_only: false,
+            modification: None,
+        }
+    }
+}
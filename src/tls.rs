@@ -0,0 +1,29 @@
+use std::error::Error;
+use std::fs;
+use std::path::Path;
+
+/// Checks that the certificate chain and private key given on the command line are PEM files of
+/// the right kind, so that a mix-up is reported with the file name instead of Rocket's
+/// "malformed PEM file".
+pub fn check_pem_files(certs: &Path, key: &Path) -> Result<(), Box<dyn Error>> {
+    let read = |path: &Path| {
+        fs::read_to_string(path).map_err(|e| format!("could not read {}: {}", path.display(), e))
+    };
+
+    if !read(certs)?.contains("-----BEGIN CERTIFICATE-----") {
+        return Err(format!("{} contains no PEM certificate", certs.display()).into());
+    }
+
+    let key_pem = read(key)?;
+    // PKCS#8 ("BEGIN PRIVATE KEY") or PKCS#1 ("BEGIN RSA PRIVATE KEY")
+    let is_key = key_pem.lines().any(|line| {
+        line.starts_with("-----BEGIN ")
+            && line.ends_with("PRIVATE KEY-----")
+            && !line.contains("ENCRYPTED")
+    });
+    if !is_key {
+        return Err(format!("{} contains no unencrypted PEM private key", key.display()).into());
+    }
+
+    Ok(())
+}
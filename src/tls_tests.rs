@@ -0,0 +1,100 @@
+use super::*;
+use std::process::Command;
+
+// Writes a self-signed certificate and its key for localhost into the directory.
+fn self_signed_certificate(dir: &Path) -> (PathBuf, PathBuf) {
+    fs::create_dir_all(dir).unwrap();
+    let certs = dir.join("cert.pem");
+    let key = dir.join("key.pem");
+
+    let status = Command::new("openssl")
+        .args(&[
+            "req", "-x509", "-newkey", "rsa:2048", "-nodes", "-days", "1",
+        ])
+        .args(&["-subj", "/CN=localhost"])
+        .arg("-keyout")
+        .arg(&key)
+        .arg("-out")
+        .arg(&certs)
+        .output()
+        .expect("openssl is needed to create a test certificate")
+        .status;
+    assert!(status.success());
+
+    (certs, key)
+}
+
+#[test]
+fn check_pem_files_test() {
+    let dir = std::env::temp_dir().join(format!("pem-test-{}", std::process::id()));
+    let (certs, key) = self_signed_certificate(&dir);
+
+    assert!(check_pem_files(&certs, &key).is_ok());
+    // Certificate and key mixed up
+    assert!(check_pem_files(&key, &certs).is_err());
+    assert!(check_pem_files(&certs, &certs).is_err());
+    assert!(check_pem_files(&certs, &dir.join("missing.pem")).is_err());
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+// Parses the tls arguments of the server subcommand.
+fn tls_params(args: &[&str]) -> ArgMatches<'static> {
+    App::new("test")
+        .arg(
+            Arg::with_name("tls certs")
+                .long("tls-certs")
+                .takes_value(true),
+        )
+        .arg(Arg::with_name("tls key").long("tls-key").takes_value(true))
+        .get_matches_from(std::iter::once("test").chain(args.iter().cloned()))
+}
+
+fn with_tls_files(certs: &Path, key: &Path) -> Result<ConfigBuilder, Box<dyn Error>> {
+    with_tls(
+        Config::build(Environment::Development),
+        &tls_params(&[
+            "--tls-certs",
+            certs.to_str().unwrap(),
+            "--tls-key",
+            key.to_str().unwrap(),
+        ]),
+    )
+}
+
+#[test]
+fn with_tls_test() {
+    let dir = std::env::temp_dir().join(format!("with-tls-test-{}", std::process::id()));
+    let (certs, key) = self_signed_certificate(&dir);
+
+    let config = with_tls_files(&certs, &key).unwrap().finalize().unwrap();
+    assert!(config.tls_enabled());
+
+    let config = with_tls(Config::build(Environment::Development), &tls_params(&[]))
+        .unwrap()
+        .finalize()
+        .unwrap();
+    assert!(!config.tls_enabled());
+
+    let error = with_tls_files(&certs, &dir.join("missing.pem"))
+        .err()
+        .unwrap();
+    assert!(error.to_string().contains("missing.pem"));
+
+    // Not PEM at all, reported with the file name
+    let not_pem = dir.join("not-pem.pem");
+    fs::write(&not_pem, "certificate").unwrap();
+    let error = with_tls_files(&not_pem, &key).err().unwrap();
+    assert!(error.to_string().contains("not-pem.pem"));
+
+    // PEM markers around a broken body are left to Rocket
+    let bad_pem = dir.join("bad.pem");
+    fs::write(
+        &bad_pem,
+        "-----BEGIN CERTIFICATE-----\nnot base64\n-----END CERTIFICATE-----\n",
+    )
+    .unwrap();
+    assert!(with_tls_files(&bad_pem, &key).unwrap().finalize().is_err());
+
+    fs::remove_dir_all(&dir).unwrap();
+}
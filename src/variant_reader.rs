@@ -2,7 +2,9 @@ extern crate bit_vec;
 extern crate rust_htslib;
 
 use regex::Regex;
+use region::UnknownChromosome;
 use rust_htslib::bcf::Read;
+use std::error::Error;
 use std::path::Path;
 
 #[derive(Serialize, Clone, Debug, PartialEq)]
@@ -24,6 +26,30 @@ pub enum VariantType {
     Variant,
 }
 
+/// The sequence change of one alternative allele of a vcf record.
+#[derive(Clone, Debug, PartialEq)]
+pub struct VariantAlleles {
+    pub(crate) position: i64,
+    pub(crate) reference: String,
+    pub(crate) alternative: String,
+}
+
+impl VariantAlleles {
+    /// Only alleles given as bases can be compared with reads, not symbolic alleles like `<DEL>`,
+    /// breakends or the `*` of an overlapping deletion.
+    pub fn new(position: i64, reference: &[u8], alternative: &[u8]) -> Option<VariantAlleles> {
+        if !alternative.is_empty() && alternative.iter().all(|b| b.is_ascii_alphabetic()) {
+            Some(VariantAlleles {
+                position: position,
+                reference: String::from_utf8_lossy(reference).into_owned(),
+                alternative: String::from_utf8_lossy(alternative).into_owned(),
+            })
+        } else {
+            None
+        }
+    }
+}
+
 #[derive(Serialize, Clone, Debug, PartialEq)]
 pub struct Report {
     pub(crate) id: String,
@@ -34,17 +60,34 @@ pub struct Report {
     pub(crate) ann: Option<Vec<Vec<String>>>,
 }
 
-pub fn read_indexed_vcf(path: &Path, chrom: String, from: u64, to: u64) -> Vec<Variant> {
-    let mut vcf = rust_htslib::bcf::IndexedReader::from_path(&path).unwrap();
+pub fn read_indexed_vcf(
+    path: &Path,
+    chrom: String,
+    from: u64,
+    to: u64,
+) -> Result<Vec<Variant>, Box<dyn Error>> {
+    let mut vcf = rust_htslib::bcf::IndexedReader::from_path(&path)?;
+
+    fetch_variants(&mut vcf, chrom, from, to)
+}
 
-    let rid = vcf.header().name2rid(chrom.as_bytes()).unwrap();
+pub fn fetch_variants(
+    vcf: &mut rust_htslib::bcf::IndexedReader,
+    chrom: String,
+    from: u64,
+    to: u64,
+) -> Result<Vec<Variant>, Box<dyn Error>> {
+    let rid = match vcf.header().name2rid(chrom.as_bytes()) {
+        Ok(rid) => rid,
+        Err(_) => return Err(UnknownChromosome(chrom).into()),
+    };
 
-    vcf.fetch(rid, from, to).unwrap();
+    vcf.fetch(rid, from, to)?;
 
     let mut variants: Vec<Variant> = Vec::new();
 
     for r in vcf.records() {
-        let mut rec = r.unwrap();
+        let mut rec = r?;
 
         let pos = rec.pos();
         let end_pos = match rec.info(b"END").integer() {
@@ -59,7 +102,7 @@ pub fn read_indexed_vcf(path: &Path, chrom: String, from: u64, to: u64) -> Vec<V
         let alleles = rec.alleles();
 
         let ref_vec = alleles[0].to_owned();
-        let rfrce = String::from_utf8(ref_vec).unwrap();
+        let rfrce = String::from_utf8(ref_vec)?;
 
         let len: u8 = rfrce.len() as u8;
 
@@ -155,5 +198,34 @@ pub fn read_indexed_vcf(path: &Path, chrom: String, from: u64, to: u64) -> Vec<V
         }
     }
 
-    variants
+    Ok(variants)
+}
+
+/// The alternative alleles of the variants in a region. Chromosomes that are missing in the vcf
+/// file have no variants.
+pub fn fetch_alleles(
+    vcf: &mut rust_htslib::bcf::IndexedReader,
+    chrom: &str,
+    from: u64,
+    to: u64,
+) -> Result<Vec<VariantAlleles>, Box<dyn Error>> {
+    let rid = match vcf.header().name2rid(chrom.as_bytes()) {
+        Ok(rid) => rid,
+        Err(_) => return Ok(Vec::new()),
+    };
+
+    vcf.fetch(rid, from, to)?;
+
+    let mut alleles = Vec::new();
+
+    for r in vcf.records() {
+        let rec = r?;
+        let record_alleles = rec.alleles();
+
+        for alt in record_alleles.iter().skip(1) {
+            alleles.extend(VariantAlleles::new(rec.pos(), record_alleles[0], alt));
+        }
+    }
+
+    Ok(alleles)
 }
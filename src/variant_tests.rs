@@ -1,6 +1,6 @@
 use super::*;
 use std::path::Path;
-use variant_reader::VariantType;
+use variant_reader::{read_indexed_vcf, VariantType};
 
 #[test]
 fn insertion_test() {
@@ -9,7 +9,8 @@ fn insertion_test() {
         String::from("11"),
         887340,
         887350,
-    );
+    )
+    .unwrap();
     let var = variants.pop().unwrap();
 
     let allel = String::from("TAAAAC");
@@ -32,7 +33,8 @@ fn duplicate_test() {
         String::from("11"),
         150000,
         151000,
-    );
+    )
+    .unwrap();
     let var = variants.pop().unwrap();
 
     let allel = String::from("ATCATC");
@@ -55,7 +57,8 @@ fn inversion_test() {
         String::from("11"),
         150000,
         151000,
-    );
+    )
+    .unwrap();
     let var = variants.pop().unwrap();
 
     let allel = String::from("CTTAG");
@@ -78,7 +81,8 @@ fn deletion_test() {
         String::from("11"),
         150000,
         151000,
-    );
+    )
+    .unwrap();
     let var = variants.pop().unwrap();
 
     let test_variant = Variant {
@@ -1,21 +1,99 @@
-async function fetchChrom(chrom, fr, to) {
-    const rs = await fetch('/api/v1/reference/' + chrom +'/' + fr + '/' + to);
+let dataset = 'default';
+
+// Identifies this viewer, so that the server keeps the rows of the reads stable while panning
+const session = Math.random().toString(36).slice(2) + Date.now().toString(36);
+
+// Nucleotide markers, including N and the IUPAC ambiguity codes, which the spec draws like N
+const NUCLEOTIDES = ['A', 'C', 'G', 'T', 'U', 'N', 'R', 'Y', 'S', 'W', 'K', 'M', 'B', 'D', 'H', 'V'];
+
+function datasetUrl(path) {
+    return 'api/v1/' + encodeURIComponent(dataset) + '/' + path;
+}
+
+async function loadDatasets() {
+    const rs = await fetch('api/v1/datasets');
     const result = await rs.json();
-    return result;
+    const select = document.getElementById('dataset');
+    select.innerHTML = '';
+    result.forEach(function (d) {
+        const option = document.createElement('option');
+        option.value = d.name;
+        option.text = d.description ? d.name + ' - ' + d.description : d.name;
+        select.appendChild(option);
+    });
+    if (result.length > 0 && !result.some(function (d) { return d.name === dataset; })) {
+        dataset = result[0].name;
+    }
+    select.value = dataset;
+}
+
+async function selectDataset(name) {
+    dataset = name;
+    // variant rows belong to the previous dataset
+    vars = new HashMap();
+    await buildVega(lastChrom, lastLowerBound, lastUpperBound);
 }
 
-async function fetchVariants(chrom, fr, to) {
-    const rs = await fetch('/api/v1/variant/' + chrom +'/' + fr + '/' + to);
+// Query parameters for grouping and sorting the reads, empty for the default order
+function readOrderQuery() {
+    let query = '';
+    const groupBy = document.getElementById('group-by').value;
+    if (groupBy !== '') {
+        query += '&group_by=' + groupBy;
+    }
+    const sortPosition = document.getElementById('sort-by-base').value;
+    if (sortPosition !== '') {
+        // the input is 1-based like the positions shown in the viewer
+        query += '&sort_by_base=' + (parseInt(sortPosition) - 1);
+    }
+    return query;
+}
+
+// Soft clipped bases are shown unless they are switched off
+function softClipQuery() {
+    return document.getElementById('soft-clips').checked ? '' : '&soft_clips=false';
+}
+
+// Small indels are mostly sequencing errors of long reads
+function minIndelLengthQuery() {
+    const minLength = document.getElementById('min-indel-length').value;
+    return minLength === '' ? '' : '&min_indel_length=' + minLength;
+}
+
+// Only reads that support the alternative allele of a variant are shown
+function altOnlyQuery() {
+    return document.getElementById('alt-only').checked ? '&alt_only=true' : '';
+}
+
+// Reads are coloured by the chosen base modification, which also adds a per-position track
+function modificationQuery() {
+    const modification = document.getElementById('modification').value;
+    return modification === '' ? '' : '&modification=' + modification;
+}
+
+async function fetchRegion(chrom, fr, to) {
+    const rs = await fetch(datasetUrl('region/' + chrom +'/' + fr + '/' + to + '?tracks=reference,variants,alignments' + readOrderQuery() + softClipQuery() + minIndelLengthQuery() + altOnlyQuery() + modificationQuery() + '&session=' + session));
     const result = await rs.json();
+    if (result.overview === undefined) {
+        result.alignments = decodeFlags($.merge(result.matches, result.bases));
+        if (result.groups !== undefined) {
+            result.groups.forEach(function (g) {
+                result.alignments.push({marker_type: "Group", group: g.group, row: g.row});
+            });
+        }
+        if (result.methylation !== undefined) {
+            result.methylation.forEach(function (m) {
+                m.marker_type = "Methylation";
+                // above the variant rows -1 to -9
+                m.row = -10;
+                result.alignments.push(m);
+            });
+        }
+    }
     return result;
 }
 
-async function fetchAlignments(chrom, fr, to) {
-    const rs = await fetch('/api/v1/alignment/' + chrom +'/' + fr + '/' + to);
-    const result = await rs.json();
-    const r0 = result[0];
-    const r1 = result[1];
-    const r = $.merge(r1, r0);
+function decodeFlags(r) {
     r.forEach(function(a) {
         let flags = [];
         a.flags.forEach(function(b) {
@@ -50,15 +128,103 @@ async function fetchAlignments(chrom, fr, to) {
     return r;
 }
 
+async function searchLocus(query) {
+    const rs = await fetch(datasetUrl('search?q=') + encodeURIComponent(query));
+    const result = await rs.json();
+    if (result.length === 0) {
+        alert('Nothing found for ' + query);
+        return;
+    }
+    const region = result[0].region;
+    // show some context around single variants
+    const flank = result[0].locus_type === "Variant" ? 50 : 0;
+    await buildVega(region.chromosome, Math.max(0, region.from - flank), region.to + flank);
+}
+
+async function showReadDetails(chrom, name) {
+    const rs = await fetch(datasetUrl('read/' + encodeURIComponent(name) + '?chromosome=' + encodeURIComponent(chrom) + '&from=' + lastLowerBound + '&to=' + lastUpperBound));
+    const records = await rs.json();
+    const div = document.getElementById('read-details');
+    div.innerHTML = '';
+
+    const jumpTo = function (label, region) {
+        const button = document.createElement('button');
+        button.type = 'button';
+        button.className = 'btn btn-sm btn-outline-info mr-1';
+        button.textContent = label + ' ' + region.chromosome + ':' + (region.from + 1);
+        // show some context around the alignment
+        button.onclick = function () { buildVega(region.chromosome, Math.max(0, region.from - 50), region.to + 50); };
+        div.appendChild(button);
+    };
+
+    records.forEach(function (r) {
+        const table = document.createElement('table');
+        table.className = 'table table-sm';
+        const rows = [
+            ['Name', r.name],
+            ['Position', r.region.chromosome + ':' + (r.region.from + 1) + '-' + r.region.to],
+            ['CIGAR', r.cigar],
+            ['MAPQ', r.mapq],
+            ['Flags', r.flags.join(', ')],
+            ['Insert size', r.insert_size],
+            ['Sequence', r.sequence],
+            ['Qualities', r.qualities.map(function (q) { return String.fromCharCode(q + 33); }).join('')]
+        ];
+        Object.keys(r.tags).forEach(function (tag) {
+            rows.push([tag, JSON.stringify(r.tags[tag])]);
+        });
+        rows.forEach(function (row) {
+            const tr = table.insertRow();
+            tr.insertCell().textContent = row[0];
+            const value = tr.insertCell();
+            value.textContent = row[1];
+            value.style.wordBreak = 'break-all';
+        });
+        div.appendChild(table);
+
+        if (r.mate !== null) {
+            jumpTo('Mate', r.mate);
+        }
+        r.supplementary.forEach(function (s) {
+            jumpTo('Supplementary', s.region);
+        });
+    });
+}
+
 async function fetchVegaSpecs() {
     const vlSpec = await fetch( "vegaSpecs.json");
     return vlSpec;
 }
 
+async function fetchOverviewSpecs() {
+    const vlSpec = await fetch( "overviewSpecs.json");
+    return vlSpec;
+}
+
+// Regions that are too large for single reads are shown as binned coverage and variant counts.
+// Zooming in far enough switches back to the detailed view.
+async function buildOverview(chrom, fr, to, data) {
+    const spec = await fetchOverviewSpecs();
+    const vlSpec = await spec.json();
+    vlSpec.width = $(window).width() - 150;
+    vlSpec.scales[0].domain = [fr,to];
+    var v = await vegaEmbed('#vis', vlSpec);
+    v = v.view.insert("bins", data.overview);
+
+    v.addEventListener('mouseup', async function (event, item) {
+        if (!scrolling_locked) {
+            scrolling_locked = true;
+            const lowerBound = Math.max(0, Math.round(v.getState().signals.grid.start_position[0]));
+            const upperBound = Math.round(v.getState().signals.grid.start_position[1]);
+            await buildVega(chrom, lowerBound, upperBound);
+            scrolling_locked = false;
+        }
+    });
+}
+
+let lastChrom;
 let lastLowerBound;
 let lastUpperBound;
-let reads = new HashMap();
-let rows;
 let vars = new HashMap();
 let var_rows;
 
@@ -66,22 +232,15 @@ let scrolling_locked = false;
 
 // Embed the visualization in the container with id `vis`
 async function buildVega(chrom, fr, to) {
+    lastChrom = chrom;
     lastLowerBound = fr;
     lastUpperBound = to;
 
-    rows = [];
-
     var_rows = [];
 
 
 
 
-    for (let i = 1; i < 40; i++) {
-        let r = {min_start: -1, max_end: 0};
-
-        rows.push(r);
-    }
-
     for (let j = 1; j < 11; j++) {
         let v = {min_start: -1.0, max_end: 0.0};
 
@@ -91,42 +250,16 @@ async function buildVega(chrom, fr, to) {
 
 
 
-    const genom = await fetchChrom(chrom, fr, to);
-    const body = await genom;
-
-    const variants = await fetchVariants(chrom, fr, to);
-    const vabody = await variants;
-
-    const al = await fetchAlignments(chrom, fr, to);
-    const albody = await al;
-
+    const data = await fetchRegion(chrom, fr, to);
+    if (data.overview !== undefined) {
+        await buildOverview(chrom, fr, to, data);
+        return;
+    }
+    const body = data.reference;
+    const vabody = data.variants;
+    const albody = data.alignments;
 
-    albody.forEach(function (a) {
-        if(reads.has(a.name)) {
-            a.row = reads.get(a.name).row;
-        } else {
-            for (i = 1; i < 39; i++) {
-                if (rows[i].min_start === -1) { //read zeile ist leer
-                    a.row = i;
-                    rows[i].min_start = a.read_start;
-                    rows[i].max_end = a.read_end;
-                    reads.set(a.name,a);
-                    break;
-                } else if (rows[i].max_end < a.read_start) {
-                    a.row = i;
-                    rows[i].max_end = a.read_end;
-                    reads.set(a.name,a);
-                    break;
-                } else if (rows[i].min_start > a.read_end) {
-                    a.row = i;
-                    rows[i].min_start = a.read_start;
-                    reads.set(a.name,a);
-                    break;
-                }
-            }
-        }
 
-    });
 
     vabody.sort(function(a, b) {
         return a.start_position < b.start_position;
@@ -165,13 +298,17 @@ async function buildVega(chrom, fr, to) {
 
 
     cont.forEach(function (a) {
-        if (a.marker_type === "A" || a.marker_type === "G" || a.marker_type === "T" || a.marker_type === "C") {
+        if (NUCLEOTIDES.includes(a.marker_type)) {
             a.base = a.marker_type;
-        } else if (a.marker_type === "Deletion" || a.marker_type === "Match" || a.marker_type === "Insertion" || a.marker_type === "Pairing" || a.marker_type === "Inversion" || a.marker_type === "Duplicate") {
+        } else if (a.marker_type === "Deletion" || a.marker_type === "Match" || a.marker_type === "Insertion" || a.marker_type === "Pairing" || a.marker_type === "Inversion" || a.marker_type === "Duplicate" || a.marker_type === "SoftClip" || a.marker_type === "Split" || a.marker_type === "SplitEnd") {
             a.typ = a.marker_type;
         }
         if (a.marker_type === "Insertion") {
             a.inserts = a.bases;
+        } else if (a.marker_type === "SoftClip") {
+            a.clipped = a.bases;
+        } else if (a.marker_type === "SplitEnd") {
+            a.partners = a.bases;
         }
     });
 
@@ -182,9 +319,15 @@ async function buildVega(chrom, fr, to) {
     const vlSpec = await spec.json();
     vlSpec.width = $(window).width() - 150;
     vlSpec.scales[0].domain = [fr,to];
+    vlSpec.signals.find(s => s.name === 'color_by_strand').value = document.getElementById('strand-colors').checked;
     var v = await vegaEmbed('#vis', vlSpec);
     v = v.view.insert("fasta", cont);
 
+    v.addEventListener('click', async function (event, item) {
+        if (item && item.datum && item.datum.name !== undefined && item.datum.read_start !== undefined) {
+            await showReadDetails(chrom, item.datum.name);
+        }
+    });
 
     v.addEventListener('mouseup', async function (event, item) {
         if (!scrolling_locked) {
@@ -192,20 +335,22 @@ async function buildVega(chrom, fr, to) {
             const lowerBound = Math.round(v.getState().signals.grid.start_position[0]);
             const upperBound = Math.round(v.getState().signals.grid.start_position[1]);
 
+            // Grouped, sorted and filtered rows only fit the requested region, so the whole view is reloaded
+            if (upperBound - lowerBound > data.level_of_detail.max_detail_span || readOrderQuery() !== '' || altOnlyQuery() !== '') {
+                await buildVega(chrom, Math.max(0, lowerBound), upperBound);
+                scrolling_locked = false;
+                return;
+            }
             var upd1 = [];
             var upd2 = [];
             var upd = [];
 
 
             if (lastUpperBound < upperBound) {
-                const n = await fetchChrom(chrom, lastUpperBound, upperBound);
-                const upper_upd_ref = await n;
-
-                const l = await fetchVariants(chrom, lastUpperBound, upperBound);
-                const upper_upd_var = await l;
-
-                const m = await fetchAlignments(chrom, lastUpperBound, upperBound);
-                var upper_upd_al = await m;
+                const upper_upd = await fetchRegion(chrom, lastUpperBound, upperBound);
+                const upper_upd_ref = upper_upd.reference;
+                const upper_upd_var = upper_upd.variants;
+                var upper_upd_al = upper_upd.alignments;
 
 
                 upper_upd_var.forEach(function (a) {
@@ -234,31 +379,6 @@ async function buildVega(chrom, fr, to) {
                     }
                 });
 
-                upper_upd_al.forEach(function (a) {
-                    if(reads.has(a.name)) {
-                        a.row = reads.get(a.name).row;
-                    } else {
-                        for (i = 1; i < 39; i++) {
-                            if (rows[i].min_start === -1) { //read zeile ist leer
-                                a.row = i;
-                                rows[i].min_start = a.read_start;
-                                rows[i].max_end = a.read_end;
-                                reads.set(a.name,a);
-                                break;
-                            } else if (rows[i].max_end < a.read_start) {
-                                a.row = i;
-                                rows[i].max_end = a.read_end;
-                                reads.set(a.name,a);
-                                break;
-                            } else if (rows[i].min_start > a.read_end) {
-                                a.row = i;
-                                rows[i].min_start = a.read_start;
-                                reads.set(a.name,a);
-                                break;
-                            }
-                        }
-                    }
-                });
 
                 var with_variants = $.merge(upper_upd_al, upper_upd_var);
                 upd1 = $.merge(with_variants, upper_upd_ref);
@@ -274,14 +394,10 @@ async function buildVega(chrom, fr, to) {
             }
 
             if (lastLowerBound > lowerBound) {
-                const o = await fetchChrom(chrom, lowerBound, lastLowerBound);
-                const lower_upd_ref = await o;
-
-                const q = await fetchVariants(chrom, lowerBound, lastLowerBound);
-                const lower_upd_var = await q;
-
-                const p = await fetchAlignments(chrom, lowerBound, lastLowerBound);
-                var lower_upd_al = await p;
+                const lower_upd = await fetchRegion(chrom, lowerBound, lastLowerBound);
+                const lower_upd_ref = lower_upd.reference;
+                const lower_upd_var = lower_upd.variants;
+                var lower_upd_al = lower_upd.alignments;
 
                 lower_upd_var.sort(function (a, b) {
                     return a.start_position < b.start_position;
@@ -314,35 +430,6 @@ async function buildVega(chrom, fr, to) {
                     }
                 });
 
-                lower_upd_al.sort(function (a, b) {
-                    return a.read_start < b.read_start;
-                });
-
-                lower_upd_al.forEach(function (a) {
-                    if(reads.has(a.name)) {
-                        a.row = reads.get(a.name).row;
-                    } else {
-                        for (i = 1; i < 39; i++) {
-                            if (rows[i].min_start === -1) { //read zeile ist leer
-                                a.row = i;
-                                rows[i].min_start = a.read_start;
-                                rows[i].max_end = a.read_end;
-                                reads.set(a.name,a);
-                                break;
-                            } else if (rows[i].max_end < a.read_start) {
-                                a.row = i;
-                                rows[i].max_end = a.read_end;
-                                reads.set(a.name,a);
-                                break;
-                            } else if (rows[i].min_start > a.read_end) {
-                                a.row = i;
-                                rows[i].min_start = a.read_start;
-                                reads.set(a.name,a);
-                                break;
-                            }
-                        }
-                    }
-                });
 
                 let with_variants2 = $.merge(lower_upd_al, lower_upd_var);
                 upd2 = $.merge(with_variants2, lower_upd_ref);
@@ -369,13 +456,17 @@ async function buildVega(chrom, fr, to) {
             upd = $.merge(upd1, upd2);
 
             upd.forEach(function (a) {
-                if (a.marker_type === "A" || a.marker_type === "G" || a.marker_type === "T" || a.marker_type === "C") {
+                if (NUCLEOTIDES.includes(a.marker_type)) {
                     a.base = a.marker_type;
-                } else if (a.marker_type === "Deletion" || a.marker_type === "Match" || a.marker_type === "Insertion" || a.marker_type === "Pairing" || a.marker_type === "Inversion" || a.marker_type === "Duplicate") {
+                } else if (a.marker_type === "Deletion" || a.marker_type === "Match" || a.marker_type === "Insertion" || a.marker_type === "Pairing" || a.marker_type === "Inversion" || a.marker_type === "Duplicate" || a.marker_type === "SoftClip" || a.marker_type === "Split" || a.marker_type === "SplitEnd") {
                     a.typ = a.marker_type;
                 }
                 if (a.marker_type === "Insertion") {
                     a.inserts = a.bases;
+                } else if (a.marker_type === "SoftClip") {
+                    a.clipped = a.bases;
+                } else if (a.marker_type === "SplitEnd") {
+                    a.partners = a.bases;
                 }
             });
 